@@ -1,92 +1,40 @@
-use std::{
-    fs::File,
-    io::{BufRead, BufReader},
-};
+use std::fs;
 
 use neural_net::{
-    micrograd::{visualize_network, Value, MLP},
+    micrograd::{
+        codegen::mlp_to_rust,
+        loss::{hinge, Reduction},
+        metrics::{self, threshold, ConfusionMatrix},
+        param_groups, train_test_split, visualize_network, CsvDataset, Dataset, InMemoryDataset,
+        LinearLR, Logger, Module, Regularization, Trainer, Value, MLP, SGD,
+    },
     Result,
 };
 
-pub struct DataPoint {
-    x: f64,
-    y: f64,
-    label: f64,
+/// Class 1 for positive scores or labels, class 0 otherwise.
+fn classes(rows: &[Vec<f64>]) -> Vec<usize> {
+    threshold(&rows.iter().map(|row| row[0]).collect::<Vec<_>>(), 0.0)
 }
 
-fn read_csv_file(filename: &str) -> Result<Vec<DataPoint>> {
-    let file = File::open(filename)?;
-    let reader = BufReader::new(file);
-    let data_points: Vec<DataPoint> = reader
-        .lines()
-        .enumerate()
-        .filter(|&(index, _)| index != 0)
-        .map(|(_, line)| {
-            let value = line.expect("line cannot be read");
-            let fields: Vec<&str> = value.split(',').collect();
-            let data_point = DataPoint {
-                x: fields[0].parse::<f64>().expect("field 0 can be read"),
-                y: fields[1].parse::<f64>().expect("field 1 can be read"),
-                label: fields[2].parse::<f64>().expect("field 2 can be read"),
-            };
-            data_point
-        })
-        .collect();
-
-    Ok(data_points)
+fn accuracy(outputs: &[Vec<f64>], labels: &[Vec<f64>]) -> f64 {
+    metrics::accuracy(&classes(outputs), &classes(labels))
 }
 
-fn load_moon_data() -> (Vec<Vec<f64>>, Vec<f64>) {
-    let data_points = read_csv_file("make_moons.csv").expect("moons file should exist");
-
-    let (data, labels) = data_points.iter().fold(
-        (Vec::new(), Vec::new()),
-        |mut acc: (Vec<Vec<f64>>, Vec<f64>), data_point| {
-            acc.0.push(vec![data_point.x, data_point.y]);
-            acc.1.push(data_point.label);
-            acc
-        },
-    );
-    (data, labels)
-}
-
-fn loss(model: &MLP, data: &[Vec<f64>], labels: &[f64], alpha: f64) -> (Value, f64) {
-    let inputs: Vec<Vec<Value>> = data
-        .iter()
-        .map(|row| vec![Value::from(row[0]), Value::from(row[1])])
-        .collect();
-
-    let scores: Vec<Value> = inputs
-        .iter()
-        .map(|row| model.forward(row.clone())[0].clone())
-        .collect();
-    let losses: Vec<Value> = labels
+fn evaluate(model: &MLP, dataset: &InMemoryDataset) {
+    let scores: Vec<Vec<f64>> = dataset
+        .inputs()
         .iter()
-        .zip(&scores)
-        .map(|(label, score)| {
-            let label_value = Value::from(*label);
-            (&Value::from(1.0) - &(&label_value * score)).relu()
+        .map(|row| {
+            vec![model.forward(&row.iter().map(|&x| Value::from(x)).collect::<Vec<_>>())[0].data()]
         })
         .collect();
-    let n = Value::from((&losses).len() as f64);
-    let data_losses = &losses.into_iter().sum::<Value>() / &n;
-
-    let reg_loss = &Value::from(alpha)
-        * &model
-            .parameters()
-            .iter()
-            .map(|p| p * p)
-            .into_iter()
-            .sum::<Value>();
-    let total_loss = &data_losses + &reg_loss;
-    let accuracies: Vec<bool> = labels
-        .iter()
-        .zip(scores.iter())
-        .map(|(label, score)| (*label > 0.0) == (score.borrow().data > 0.0))
-        .collect();
-
-    let accuracy = accuracies.iter().filter(|&a| *a).count() as f64 / n.borrow().data;
-    (total_loss, accuracy)
+    let (predicted, labels) = (classes(&scores), classes(dataset.targets()));
+    let matrix = ConfusionMatrix::from_predictions(&predicted, &labels);
+    println!("test accuracy {:.2}%", matrix.accuracy() * 100.0);
+    println!("test f1 {:.4}", matrix.f1(metrics::Average::Binary(1)));
+    let scores: Vec<f64> = scores.iter().map(|s| s[0]).collect();
+    println!("test roc auc {:.4}", metrics::roc_auc(&scores, &labels));
+    print!("{matrix}");
 }
 
 fn plot_ascii(model: &MLP, bound: isize) {
@@ -94,7 +42,7 @@ fn plot_ascii(model: &MLP, bound: isize) {
     for y in -bound..bound {
         let mut row: Vec<String> = Vec::new();
         for x in -bound..bound {
-            let k = &model.forward(vec![
+            let k = &model.forward(&[
                 Value::from(x as f64 / bound as f64 * 2.0),
                 Value::from(-y as f64 / bound as f64 * 2.0),
             ])[0];
@@ -115,28 +63,54 @@ fn plot_ascii(model: &MLP, bound: isize) {
     }
 }
 
+const MODEL_PATH: &str = "./moons_model.bin";
+
+fn train(train: &dyn Dataset, validation: &dyn Dataset) -> Result<MLP> {
+    let mut model = MLP::new(vec![2, 16, 16, 1]);
+    print!("{}", model.summary());
+    // SVM hinge loss, with the optimizer applying L2 regularization to the weights only
+    let loss = |scores: &[Vec<Value>], labels: &[Vec<f64>]| {
+        let scores: Vec<Value> = scores.iter().map(|s| s[0].clone()).collect();
+        let labels: Vec<f64> = labels.iter().map(|l| l[0]).collect();
+        hinge(&scores, &labels, Reduction::Mean)
+    };
+    let groups = param_groups(model.named_parameters(), |name| {
+        if name.ends_with("bias") {
+            Regularization::None
+        } else {
+            Regularization::L2(0.001)
+        }
+    });
+    Trainer::new(&mut model, SGD::from_groups(groups, 1.0), loss)
+        .with_scheduler(LinearLR::new(1.0, 0.1, 100))
+        .with_metric("accuracy", accuracy)
+        .with_validation(validation)
+        .with_callback(Logger::new().every(10))
+        .fit(train, 100)?;
+    Ok(model)
+}
+
 fn main() -> Result<()> {
-    let model = MLP::new(vec![2, 16, 16, 1]);
-    let (data, labels) = load_moon_data();
-    let alpha = 0.001;
-    for k in 0..100 {
-        let (total_loss, accuracy) = loss(&model, &data, &labels, alpha);
-        model.zero_grad();
-        total_loss.backward();
-        let learning_rate = 1.0 - 0.9 * (k as f64) / 100.0;
-        for p in &model.parameters() {
-            let delta = learning_rate * p.borrow().grad;
-            p.borrow_mut().data -= delta;
+    let moons = CsvDataset::builder("make_moons.csv")
+        .with_inputs(["x", "y"])
+        .with_targets(["label"])
+        .load()?;
+    let (train_set, test_set) = train_test_split(&moons, 0.2, 7)?;
+    let model = match MLP::load(MODEL_PATH) {
+        Ok(model) => {
+            println!("loaded {MODEL_PATH}, delete it to retrain");
+            model
         }
-        println!(
-            "step {k} loss {:.3}, accuracy {:.2}%, learning rate: {:.3}",
-            total_loss.borrow().data,
-            accuracy * 100.0,
-            learning_rate
-        )
-    }
+        Err(_) => {
+            let model = train(&train_set, &test_set)?;
+            model.save(MODEL_PATH)?;
+            model
+        }
+    };
+    evaluate(&model, &test_set);
     plot_ascii(&model, 20);
     let input = vec![Value::from(0.0), Value::from(0.0)];
-    let _ = visualize_network(model.forward(input), "./moon_graph.png".to_string());
+    let _ = visualize_network(model.forward(&input), "./moon_graph.png".to_string());
+    fs::write("./moons_predictor.rs", mlp_to_rust(&model, true)?)?;
     Ok(())
 }
@@ -25,7 +25,7 @@ fn main() -> Result<()> {
     println!("{:?}", encoder);
     // println!("last encoded string: {:?}", encoded);
 
-    let decoded = encoder.decode(encoded)?;
+    let _decoded = encoder.decode(encoded)?;
     // println!("decoded string: {:?}", decoded);
 
     Ok(())
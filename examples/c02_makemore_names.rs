@@ -0,0 +1,165 @@
+//! A character-level language model in the style of makemore, trained on `names.txt`.
+//!
+//! A count-based bigram model sets the baseline, then an MLP reads a window of the previous
+//! characters through an `Embedding` and predicts the next one. Both report their loss on
+//! held-out names, and the MLP samples new ones.
+//!
+//! Training takes a couple of minutes with `cargo run --release --example c02_makemore_names`.
+
+use std::{collections::BTreeSet, fs};
+
+use neural_net::{
+    micrograd::{
+        loss::{cross_entropy, Reduction},
+        softmax, train_test_split, Activation, Adam, Dataset, InMemoryDataset, Init, Layer, Logger,
+        Module, Sequential, Trainer, Value,
+    },
+    transformer::Embedding,
+    Result,
+};
+use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, SeedableRng};
+
+/// Characters of context the MLP sees.
+const CONTEXT: usize = 3;
+const EMBEDDING_DIM: usize = 6;
+const HIDDEN: usize = 48;
+
+/// Maps characters to token IDs, with ID 0 for `.`, which marks the start and end of a name.
+struct Vocabulary {
+    chars: Vec<char>,
+}
+
+impl Vocabulary {
+    fn new(names: &[&str]) -> Vocabulary {
+        let chars: BTreeSet<char> = names.iter().flat_map(|name| name.chars()).collect();
+        Vocabulary {
+            chars: std::iter::once('.').chain(chars).collect(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.chars.len()
+    }
+
+    fn id(&self, c: char) -> usize {
+        self.chars.iter().position(|&x| x == c).expect("known char")
+    }
+
+    /// One sample per character and the end marker, each the `CONTEXT` ids before it.
+    fn samples(&self, names: &[&str]) -> Result<InMemoryDataset> {
+        let mut inputs = Vec::new();
+        let mut targets = Vec::new();
+        for name in names {
+            let mut context = [0; CONTEXT];
+            for id in name.chars().map(|c| self.id(c)).chain([0]) {
+                inputs.push(context.iter().map(|&id| id as f64).collect());
+                targets.push(vec![id as f64]);
+                context.rotate_left(1);
+                context[CONTEXT - 1] = id;
+            }
+        }
+        Ok(InMemoryDataset::new(inputs, targets)?)
+    }
+}
+
+fn labels(targets: &[Vec<f64>]) -> Vec<usize> {
+    targets.iter().map(|t| t[0] as usize).collect()
+}
+
+/// Mean negative log likelihood of a smoothed bigram count model.
+fn bigram_baseline(vocab: &Vocabulary, train: &dyn Dataset, validation: &dyn Dataset) -> f64 {
+    let mut counts = vec![vec![1.0; vocab.len()]; vocab.len()];
+    for i in 0..train.len() {
+        let (context, next) = train.get(i);
+        counts[context[CONTEXT - 1] as usize][next[0] as usize] += 1.0;
+    }
+    let nll: f64 = (0..validation.len())
+        .map(|i| {
+            let (context, next) = validation.get(i);
+            let row = &counts[context[CONTEXT - 1] as usize];
+            -(row[next[0] as usize] / row.iter().sum::<f64>()).ln()
+        })
+        .sum();
+    nll / validation.len() as f64
+}
+
+fn sample(model: &Sequential, vocab: &Vocabulary, rng: &mut StdRng) -> String {
+    let mut context = [0; CONTEXT];
+    let mut name = String::new();
+    loop {
+        let input: Vec<Value> = context.iter().map(|&id| Value::from(id as f64)).collect();
+        let probs: Vec<f64> = softmax(&model.forward(&input))
+            .iter()
+            .map(Value::data)
+            .collect();
+        let id = WeightedIndex::new(&probs)
+            .expect("valid distribution")
+            .sample(rng);
+        if id == 0 || name.len() >= 20 {
+            return name;
+        }
+        name.push(vocab.chars[id]);
+        context.rotate_left(1);
+        context[CONTEXT - 1] = id;
+    }
+}
+
+fn main() -> Result<()> {
+    let text = fs::read_to_string("names.txt")?;
+    let names: Vec<&str> = text.lines().filter(|line| !line.is_empty()).collect();
+    let vocab = Vocabulary::new(&names);
+    let samples = vocab.samples(&names)?;
+    let (train, validation) = train_test_split(&samples, 0.1, 42)?;
+    println!(
+        "{} names, {} characters, {} training and {} validation samples",
+        names.len(),
+        vocab.len(),
+        train.len(),
+        validation.len()
+    );
+    println!(
+        "bigram baseline val_loss {:.4}",
+        bigram_baseline(&vocab, &train, &validation)
+    );
+
+    let mut rng = StdRng::seed_from_u64(2024);
+    let mut model = Sequential::new()
+        .with_module(Embedding::with_init(
+            vocab.len(),
+            EMBEDDING_DIM,
+            Init::Normal(0.0, 1.0),
+            &mut rng,
+        ))
+        .with_module(Layer::with_init(
+            CONTEXT * EMBEDDING_DIM,
+            HIDDEN,
+            Activation::Tanh,
+            Init::XavierNormal,
+            &mut rng,
+        ))
+        .with_module(Layer::with_init(
+            HIDDEN,
+            vocab.len(),
+            Activation::Identity,
+            Init::Normal(0.0, 0.01),
+            &mut rng,
+        ));
+    println!("mlp with {} parameters", model.parameters().len());
+
+    let loss = |logits: &[Vec<Value>], targets: &[Vec<f64>]| {
+        cross_entropy(logits, &labels(targets), Reduction::Mean)
+    };
+    let optimizer = Adam::new(model.parameters(), 0.01);
+    Trainer::new(&mut model, optimizer, loss)
+        .with_batch_size(64)
+        .with_shuffle(7)
+        .with_validation(&validation)
+        .with_callback(Logger::new())
+        .fit(&train, 5)?;
+
+    println!("samples:");
+    for _ in 0..10 {
+        println!("  {}", sample(&model, &vocab, &mut rng));
+    }
+    Ok(())
+}
@@ -0,0 +1,114 @@
+//! A small GPT trained on `names.txt`, tokenized with `BytePairEncoder`.
+//!
+//! Training loss on random windows and loss on held-out text are printed as it learns, then
+//! new names are generated with a few sampling strategies. Run it with
+//! `cargo run --release --example c03_gpt_names`.
+
+use std::{collections::BTreeMap, fs};
+
+use neural_net::{
+    micrograd::{Adam, Module, Optimizer},
+    transformer::{BytePairEncoder, Encoding, Sampler, GPT},
+    Result,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const CONTEXT: usize = 12;
+const STEPS: usize = 600;
+
+/// Renumbers the encoder's token IDs densely, since most byte-level IDs never occur.
+struct Tokens {
+    to_dense: BTreeMap<usize, usize>,
+    to_encoder: Vec<usize>,
+}
+
+impl Tokens {
+    fn new(ids: &[usize]) -> Tokens {
+        let mut to_encoder = ids.to_vec();
+        to_encoder.sort_unstable();
+        to_encoder.dedup();
+        let to_dense = to_encoder
+            .iter()
+            .enumerate()
+            .map(|(i, &id)| (id, i))
+            .collect();
+        Tokens {
+            to_dense,
+            to_encoder,
+        }
+    }
+
+    fn dense(&self, ids: &[usize]) -> Vec<usize> {
+        ids.iter().map(|id| self.to_dense[id]).collect()
+    }
+
+    fn encoder(&self, ids: &[usize]) -> Vec<usize> {
+        ids.iter().map(|&id| self.to_encoder[id]).collect()
+    }
+}
+
+/// Mean loss over consecutive windows of `ids`.
+fn evaluate(gpt: &GPT, ids: &[usize]) -> f64 {
+    let windows: Vec<&[usize]> = ids.chunks(CONTEXT + 1).filter(|w| w.len() > 1).collect();
+    windows.iter().map(|w| gpt.loss(w).data()).sum::<f64>() / windows.len() as f64
+}
+
+fn main() -> Result<()> {
+    let text = fs::read_to_string("names.txt")?;
+    let mut encoder = BytePairEncoder::default();
+    encoder.train(text.clone(), Encoding::Utf8, Some(80))?;
+    let encoded = encoder.encode(&text, Encoding::Utf8)?;
+    let tokens = Tokens::new(&encoded);
+    let ids = tokens.dense(&encoded);
+    let (train, validation) = ids.split_at(ids.len() * 9 / 10);
+    println!(
+        "{} characters encoded as {} tokens from a vocabulary of {}",
+        text.len(),
+        ids.len(),
+        tokens.to_encoder.len()
+    );
+
+    let gpt = GPT::builder(tokens.to_encoder.len(), CONTEXT)
+        .with_dim(16)
+        .with_heads(2)
+        .with_layers(1)
+        .with_seed(1)
+        .build();
+    println!("gpt with {} parameters", gpt.parameters().len());
+    println!("step 0: val_loss {:.4}", evaluate(&gpt, validation));
+
+    let mut optimizer = Adam::new(gpt.parameters(), 0.01);
+    let mut rng = StdRng::seed_from_u64(7);
+    let mut running = 0.0;
+    for step in 1..=STEPS {
+        let start = rng.gen_range(0..train.len() - CONTEXT);
+        let loss = gpt.loss(&train[start..=start + CONTEXT]);
+        optimizer.zero_grad();
+        loss.backward();
+        optimizer.step();
+        running += loss.data();
+        if step % 100 == 0 {
+            println!(
+                "step {step}: loss {:.4}, val_loss {:.4}",
+                running / 100.0,
+                evaluate(&gpt, validation)
+            );
+            running = 0.0;
+        }
+    }
+
+    let newline = tokens.dense(&encoder.encode("\n", Encoding::Utf8)?);
+    let samplers = [
+        ("greedy", Sampler::new().with_temperature(0.0)),
+        ("temperature 0.8", Sampler::new().with_temperature(0.8)),
+        ("top-k 5", Sampler::new().with_top_k(5)),
+        ("top-p 0.9", Sampler::new().with_top_p(0.9)),
+    ];
+    for (name, sampler) in samplers {
+        let mut sampler = sampler.with_seed(3);
+        let generated = gpt.generate(&newline, 30, &mut sampler)?;
+        let text = encoder.decode(tokens.encoder(&generated))?;
+        println!("{name}: {:?}", text.trim().replace('\n', " "));
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+
+use super::Value;
+
+/// The nonlinearity a layer applies to each neuron's weighted sum.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Activation {
+    #[default]
+    Identity,
+    Relu,
+    Tanh,
+    Sigmoid,
+    /// ReLU that scales negative inputs by the given slope instead of zeroing them.
+    LeakyRelu(f64),
+}
+
+impl Activation {
+    pub fn apply(&self, x: &Value) -> Value {
+        match self {
+            Activation::Identity => x.clone(),
+            Activation::Relu => x.relu(),
+            Activation::Tanh => x.tanh(),
+            Activation::Sigmoid => x.sigmoid(),
+            Activation::LeakyRelu(slope) => x.leaky_relu(*slope),
+        }
+    }
+}
+
+impl fmt::Display for Activation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Activation::Identity => write!(f, "identity"),
+            Activation::Relu => write!(f, "relu"),
+            Activation::Tanh => write!(f, "tanh"),
+            Activation::Sigmoid => write!(f, "sigmoid"),
+            Activation::LeakyRelu(slope) => write!(f, "leaky_relu({slope})"),
+        }
+    }
+}
+
+/// `true` is the ReLU the `nonlin` flag used to stand for, `false` no activation.
+impl From<bool> for Activation {
+    fn from(nonlin: bool) -> Activation {
+        if nonlin {
+            Activation::Relu
+        } else {
+            Activation::Identity
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn activations_apply() {
+        let x = Value::from(-2.0);
+        let out: Vec<f64> = [
+            Activation::Identity,
+            Activation::Relu,
+            Activation::Tanh,
+            Activation::Sigmoid,
+            Activation::LeakyRelu(0.01),
+        ]
+        .iter()
+        .map(|activation| activation.apply(&x).data())
+        .collect();
+        let sigmoid = 1.0 / (1.0 + 2.0f64.exp());
+        assert_eq!(out, vec![-2.0, 0.0, (-2.0f64).tanh(), sigmoid, -0.02]);
+        assert_eq!(Activation::from(true), Activation::Relu);
+    }
+}
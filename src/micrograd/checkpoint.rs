@@ -0,0 +1,201 @@
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use super::{backward_from, Operation, Value, ValueData};
+
+type SegmentFn = Box<dyn Fn(&[Value]) -> Vec<Value>>;
+
+/// The recipe for a checkpointed piece of graph, kept so it can be replayed during `backward`.
+pub struct Segment {
+    function: SegmentFn,
+    seeds: RefCell<Vec<f64>>,
+}
+
+impl fmt::Debug for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Segment {{ outputs: {} }}", self.seeds.borrow().len())
+    }
+}
+
+/// Runs `function` on `inputs` without keeping its interior nodes alive.
+///
+/// Only the outputs are kept in the graph; the interior of the segment is rebuilt during
+/// `backward` and dropped again once its gradients have been handed back to `inputs`.
+/// Gradients match the non-checkpointed graph, at the cost of a second forward pass.
+///
+/// `function` must be deterministic, and every non-leaf `Value` it depends on has to be passed
+/// in through `inputs`. Leaves such as model parameters may be captured directly, their
+/// gradients are accumulated during the replay.
+pub fn checkpoint<F>(inputs: &[Value], function: F) -> Vec<Value>
+where
+    F: Fn(&[Value]) -> Vec<Value> + 'static,
+{
+    let outputs: Vec<f64> = function(&detach(inputs))
+        .iter()
+        .map(|output| output.borrow().data)
+        .collect();
+
+    let segment = Value::from(0.0);
+    segment.borrow_mut()._op = Some(Operation::Checkpoint);
+    segment.borrow_mut()._prev = inputs.to_vec();
+    segment.borrow_mut()._segment = Some(Rc::new(Segment {
+        function: Box::new(function),
+        seeds: RefCell::new(vec![0.0; outputs.len()]),
+    }));
+    segment.borrow_mut()._backward = Some(replay);
+
+    outputs
+        .into_iter()
+        .enumerate()
+        .map(|(index, data)| {
+            let output = Value::from(data);
+            output.borrow_mut()._op = Some(Operation::CheckpointOutput(index));
+            output.borrow_mut()._prev = vec![segment.clone()];
+            output.borrow_mut()._backward = Some(|val: &ValueData| {
+                let Some(Operation::CheckpointOutput(index)) = val._op else {
+                    unreachable!("checkpoint outputs keep their operation")
+                };
+                val._prev[0]
+                    .borrow()
+                    ._segment
+                    .as_ref()
+                    .expect("checkpoint output points at its segment")
+                    .seeds
+                    .borrow_mut()[index] += val.grad;
+            });
+            output
+        })
+        .collect()
+}
+
+fn detach(inputs: &[Value]) -> Vec<Value> {
+    inputs
+        .iter()
+        .map(|input| Value::from(input.borrow().data))
+        .collect()
+}
+
+fn replay(val: &ValueData) {
+    let segment = val
+        ._segment
+        .as_ref()
+        .expect("checkpoint node carries its segment");
+    let inputs = detach(&val._prev);
+    let outputs = (segment.function)(&inputs);
+    let seeds = segment.seeds.replace(vec![0.0; outputs.len()]);
+    let roots: Vec<(Value, f64)> = outputs.into_iter().zip(seeds).collect();
+    backward_from(&roots);
+    for (original, copy) in val._prev.iter().zip(&inputs) {
+        original.borrow_mut().grad += copy.borrow().grad;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micrograd::live_values;
+
+    fn dense(inputs: &[Value], weights: &[Vec<Value>]) -> Vec<Value> {
+        weights
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(inputs)
+                    .map(|(w, x)| w * x)
+                    .sum::<Value>()
+                    .relu()
+            })
+            .collect()
+    }
+
+    fn make_weights(depth: usize, width: usize) -> Vec<Vec<Vec<Value>>> {
+        (0..depth)
+            .map(|d| {
+                (0..width)
+                    .map(|i| {
+                        (0..width)
+                            .map(|j| Value::from(((d + 2 * i + 3 * j) % 7) as f64 / 7.0 - 0.2))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn run(weights: &[Vec<Vec<Value>>], inputs: &[Value], checkpointed: bool) -> (Value, usize) {
+        let before = live_values();
+        let mut activations = inputs.to_vec();
+        for layer in weights {
+            activations = if checkpointed {
+                let layer = layer.clone();
+                checkpoint(&activations, move |x| dense(x, &layer))
+            } else {
+                dense(&activations, layer)
+            };
+        }
+        let loss: Value = activations.into_iter().sum();
+        let retained = live_values() - before;
+        (loss, retained)
+    }
+
+    #[test]
+    fn checkpoint_matches_plain_gradients() {
+        let weights = make_weights(6, 5);
+        let inputs: Vec<Value> = (0..5).map(|i| Value::from(i as f64 * 0.3 + 0.1)).collect();
+        let params: Vec<Value> = weights.iter().flatten().flatten().cloned().collect();
+
+        let (loss, _) = run(&weights, &inputs, false);
+        loss.backward();
+        let plain_data = loss.borrow().data;
+        let plain: Vec<f64> = params
+            .iter()
+            .chain(&inputs)
+            .map(|p| p.borrow().grad)
+            .collect();
+
+        params
+            .iter()
+            .chain(&inputs)
+            .for_each(|p| p.borrow_mut().grad = 0.0);
+        let (loss, _) = run(&weights, &inputs, true);
+        loss.backward();
+        let checkpointed: Vec<f64> = params
+            .iter()
+            .chain(&inputs)
+            .map(|p| p.borrow().grad)
+            .collect();
+
+        assert_eq!(loss.borrow().data, plain_data);
+        assert_eq!(checkpointed, plain);
+    }
+
+    #[test]
+    fn checkpoint_keeps_fewer_nodes_alive() {
+        let weights = make_weights(8, 6);
+        let inputs: Vec<Value> = (0..6).map(|i| Value::from(i as f64 * 0.1)).collect();
+        let (_plain_loss, plain) = run(&weights, &inputs, false);
+        let (_loss, checkpointed) = run(&weights, &inputs, true);
+        assert!(
+            checkpointed * 3 < plain,
+            "checkpointed {checkpointed} vs plain {plain}"
+        );
+    }
+
+    #[test]
+    fn checkpoint_backward_twice_accumulates() {
+        let a = Value::from(3.0);
+        let out = checkpoint(std::slice::from_ref(&a), |x| vec![&x[0] * &x[0]]);
+        out[0].backward();
+        assert_eq!(a.borrow().grad, 6.0);
+        out[0].backward();
+        assert_eq!(a.borrow().grad, 12.0);
+    }
+
+    #[test]
+    fn outputs_keep_their_index_out_of_the_graph() {
+        let (a, b) = (Value::from(2.0), Value::from(5.0));
+        let out = checkpoint(&[a, b], |x| vec![&x[0] + &x[1], &x[0] * &x[1]]);
+        assert_eq!(out[1].borrow()._op, Some(Operation::CheckpointOutput(1)));
+        assert_eq!(out[1].borrow()._prev.len(), 1);
+        assert_eq!(out[1].to_expr(), "checkpoint(2, 5)[1]");
+    }
+}
@@ -0,0 +1,572 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+};
+
+use super::{Activation, Operation, Value, MLP};
+
+use crate::Result;
+
+const HEADER: &str = "// Generated by neural_net::micrograd::codegen, do not edit.\n";
+
+/// Emits Rust source for `fn predict(x: [f64; N]) -> [f64; M]` computing `outputs` from `inputs`.
+///
+/// Leaves that are not in `inputs` are baked into the source as constants. With
+/// `with_gradient`, a matching `fn gradient(x: [f64; N]) -> [[f64; N]; M]` is emitted as well,
+/// returning the derivative of every output with respect to every input.
+#[allow(clippy::mutable_key_type)]
+pub fn graph_to_rust(outputs: &[Value], inputs: &[Value], with_gradient: bool) -> Result<String> {
+    let order = topo(outputs);
+    let index: HashMap<Value, usize> = order
+        .iter()
+        .enumerate()
+        .map(|(n, v)| (v.clone(), n))
+        .collect();
+    let input_index: HashMap<Value, usize> = inputs
+        .iter()
+        .enumerate()
+        .map(|(i, v)| (v.clone(), i))
+        .collect();
+    let (n_in, n_out) = (inputs.len(), outputs.len());
+
+    let mut forward = String::new();
+    for (n, v) in order.iter().enumerate() {
+        let data = v.borrow();
+        let arg = |i: usize| format!("v{}", index[&data._prev[i]]);
+        let expr = match data._op {
+            None => match input_index.get(v) {
+                Some(i) => format!("x[{i}]"),
+                None => literal(data.data),
+            },
+            Some(Operation::Add) => format!("{} + {}", arg(0), arg(1)),
+            Some(Operation::Sub) => format!("{} - {}", arg(0), arg(1)),
+            Some(Operation::Mul) => format!("{} * {}", arg(0), arg(1)),
+            Some(Operation::Div) => format!("{} / {}", arg(0), arg(1)),
+            Some(Operation::Pow) => {
+                format!("{}.powf({})", arg(0), literal(data._prev[1].borrow().data))
+            }
+            Some(Operation::Relu) => format!("{}.max(0.0)", arg(0)),
+            Some(Operation::Max) => format!("{}.max({})", arg(0), arg(1)),
+            Some(Operation::Exp) => format!("{}.exp()", arg(0)),
+            Some(Operation::Log) => format!("{}.ln()", arg(0)),
+            Some(Operation::Abs) => format!("{}.abs()", arg(0)),
+            Some(Operation::Tanh) => format!("{}.tanh()", arg(0)),
+            Some(Operation::Sigmoid) => format!("1.0 / (1.0 + (-{}).exp())", arg(0)),
+            Some(Operation::LeakyRelu) => {
+                let slope = literal(data._prev[1].borrow().data);
+                format!(
+                    "if {a} > 0.0 {{ {a} }} else {{ {slope} * {a} }}",
+                    a = arg(0)
+                )
+            }
+            Some(Operation::Checkpoint | Operation::CheckpointOutput(_)) => return Err(
+                "checkpointed segments cannot be compiled, build the graph without `checkpoint`"
+                    .into(),
+            ),
+        };
+        writeln!(forward, "    let v{n} = {expr};")?;
+    }
+    let returned = outputs
+        .iter()
+        .map(|o| format!("v{}", index[o]))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    let mut source = String::from(HEADER);
+    writeln!(source)?;
+    writeln!(
+        source,
+        "pub fn predict(x: [f64; {n_in}]) -> [f64; {n_out}] {{"
+    )?;
+    source.push_str(&forward);
+    writeln!(source, "    [{returned}]")?;
+    writeln!(source, "}}")?;
+    if !with_gradient {
+        return Ok(source);
+    }
+
+    // a node needs an adjoint only if some input flows into it
+    let mut depends = vec![false; order.len()];
+    for (n, v) in order.iter().enumerate() {
+        let data = v.borrow();
+        depends[n] = input_index.contains_key(v)
+            || children(&data._op, &data._prev)
+                .iter()
+                .any(|c| depends[index[c]]);
+    }
+    let mut reverse = String::new();
+    for (n, v) in order.iter().enumerate().rev() {
+        let data = v.borrow();
+        let prev: Vec<usize> = children(&data._op, &data._prev)
+            .iter()
+            .map(|c| index[c])
+            .collect();
+        let mut push = |target: usize, update: String| -> Result<()> {
+            if depends[target] {
+                writeln!(reverse, "        g[{target}] {update};")?;
+            }
+            Ok(())
+        };
+        match data._op {
+            None => {}
+            Some(Operation::Add) => {
+                push(prev[0], format!("+= g[{n}]"))?;
+                push(prev[1], format!("+= g[{n}]"))?;
+            }
+            Some(Operation::Sub) => {
+                push(prev[0], format!("+= g[{n}]"))?;
+                push(prev[1], format!("-= g[{n}]"))?;
+            }
+            Some(Operation::Mul) => {
+                push(prev[0], format!("+= v{} * g[{n}]", prev[1]))?;
+                push(prev[1], format!("+= v{} * g[{n}]", prev[0]))?;
+            }
+            Some(Operation::Div) => {
+                push(prev[0], format!("+= g[{n}] / v{}", prev[1]))?;
+                push(
+                    prev[1],
+                    format!("-= g[{n}] * v{} / (v{} * v{})", prev[0], prev[1], prev[1]),
+                )?;
+            }
+            Some(Operation::Pow) => {
+                let p = literal(data._prev[1].borrow().data);
+                push(
+                    prev[0],
+                    format!("+= {p} * v{}.powf({p} - 1.0) * g[{n}]", prev[0]),
+                )?;
+            }
+            Some(Operation::Relu) => {
+                push(
+                    prev[0],
+                    format!("+= if v{n} > 0.0 {{ g[{n}] }} else {{ 0.0 }}"),
+                )?;
+            }
+            Some(Operation::Max) => {
+                let (a, b) = (prev[0], prev[1]);
+                push(a, format!("+= if v{a} >= v{b} {{ g[{n}] }} else {{ 0.0 }}"))?;
+                push(b, format!("+= if v{a} >= v{b} {{ 0.0 }} else {{ g[{n}] }}"))?;
+            }
+            Some(Operation::Exp) => push(prev[0], format!("+= v{n} * g[{n}]"))?,
+            Some(Operation::Tanh) => push(prev[0], format!("+= (1.0 - v{n} * v{n}) * g[{n}]"))?,
+            Some(Operation::Sigmoid) => {
+                push(prev[0], format!("+= v{n} * (1.0 - v{n}) * g[{n}]"))?;
+            }
+            Some(Operation::LeakyRelu) => {
+                let (a, slope) = (prev[0], literal(data._prev[1].borrow().data));
+                push(
+                    a,
+                    format!("+= if v{a} > 0.0 {{ g[{n}] }} else {{ {slope} * g[{n}] }}"),
+                )?;
+            }
+            Some(Operation::Log) => push(prev[0], format!("+= g[{n}] / v{}", prev[0]))?,
+            Some(Operation::Abs) => {
+                let a = prev[0];
+                push(
+                    a,
+                    format!("+= if v{a} > 0.0 {{ g[{n}] }} else if v{a} < 0.0 {{ -g[{n}] }} else {{ 0.0 }}"),
+                )?;
+            }
+            Some(Operation::Checkpoint | Operation::CheckpointOutput(_)) => {
+                unreachable!("rejected while emitting predict")
+            }
+        }
+    }
+    let output_nodes = outputs
+        .iter()
+        .map(|o| index[o].to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let row = inputs
+        .iter()
+        .map(|i| match index.get(i) {
+            Some(n) => format!("g[{n}]"),
+            None => "0.0".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    writeln!(source)?;
+    writeln!(source, "#[allow(unused_variables, unused_mut)]")?;
+    writeln!(
+        source,
+        "pub fn gradient(x: [f64; {n_in}]) -> [[f64; {n_in}]; {n_out}] {{"
+    )?;
+    source.push_str(&forward);
+    writeln!(source, "    let mut jacobian = [[0.0; {n_in}]; {n_out}];")?;
+    writeln!(
+        source,
+        "    for (row, output) in jacobian.iter_mut().zip([{output_nodes}]) {{"
+    )?;
+    writeln!(source, "        let mut g = [0.0f64; {}];", order.len())?;
+    writeln!(source, "        g[output] = 1.0;")?;
+    source.push_str(&reverse);
+    writeln!(source, "        *row = [{row}];")?;
+    writeln!(source, "    }}")?;
+    writeln!(source, "    jacobian")?;
+    writeln!(source, "}}")?;
+    Ok(source)
+}
+
+/// Emits Rust source for `fn predict` of a trained `MLP`, with its weights as `const` arrays.
+///
+/// With `with_gradient`, a matching `fn gradient` returning the Jacobian of the outputs with
+/// respect to the inputs is emitted as well.
+pub fn mlp_to_rust(model: &MLP, with_gradient: bool) -> Result<String> {
+    let n_in = model.layers.first().ok_or("model has no layers")?.nin;
+    let n_out = model
+        .layers
+        .last()
+        .ok_or("model has no layers")?
+        .neurons
+        .len();
+    let activated = model
+        .layers
+        .iter()
+        .any(|layer| activation_source(layer.activation()).is_some());
+
+    let mut source = String::from(HEADER);
+    for (l, layer) in model.layers.iter().enumerate() {
+        let (nin, nout) = (layer.nin, layer.neurons.len());
+        writeln!(source)?;
+        writeln!(
+            source,
+            "const LAYER_{l}_WEIGHTS: [[f64; {nin}]; {nout}] = ["
+        )?;
+        for neuron in &layer.neurons {
+            writeln!(source, "    [{}],", literals(&neuron.weights))?;
+        }
+        writeln!(source, "];")?;
+        let biases: Vec<Value> = layer.neurons.iter().map(|n| n.bias.clone()).collect();
+        writeln!(
+            source,
+            "const LAYER_{l}_BIASES: [f64; {nout}] = [{}];",
+            literals(&biases)
+        )?;
+    }
+
+    source.push_str(DENSE);
+    let mut forward = String::new();
+    for (l, layer) in model.layers.iter().enumerate() {
+        writeln!(
+            forward,
+            "    let z{} = dense(&LAYER_{l}_WEIGHTS, &LAYER_{l}_BIASES, &a{l});",
+            l + 1
+        )?;
+        let activation = match activation_source(layer.activation()) {
+            Some((f, _)) => format!("z{}.map(|v| {f})", l + 1),
+            None => format!("z{}", l + 1),
+        };
+        writeln!(forward, "    let a{} = {activation};", l + 1)?;
+    }
+
+    writeln!(source)?;
+    writeln!(
+        source,
+        "pub fn predict(x: [f64; {n_in}]) -> [f64; {n_out}] {{"
+    )?;
+    writeln!(source, "    let a0 = x;")?;
+    source.push_str(&forward);
+    writeln!(source, "    a{}", model.layers.len())?;
+    writeln!(source, "}}")?;
+    if !with_gradient {
+        return Ok(source);
+    }
+
+    source.push_str(DENSE_BACKWARD);
+    if activated {
+        source.push_str(ACTIVATION_BACKWARD);
+    }
+    writeln!(source)?;
+    writeln!(source, "#[allow(unused_variables)]")?;
+    writeln!(
+        source,
+        "pub fn gradient(x: [f64; {n_in}]) -> [[f64; {n_in}]; {n_out}] {{"
+    )?;
+    writeln!(source, "    let a0 = x;")?;
+    source.push_str(&forward);
+    writeln!(source, "    let mut jacobian = [[0.0; {n_in}]; {n_out}];")?;
+    writeln!(
+        source,
+        "    for (k, row) in jacobian.iter_mut().enumerate() {{"
+    )?;
+    writeln!(source, "        let mut g = [0.0; {n_out}];")?;
+    writeln!(source, "        g[k] = 1.0;")?;
+    for (l, layer) in model.layers.iter().enumerate().rev() {
+        if let Some((_, derivative)) = activation_source(layer.activation()) {
+            writeln!(
+                source,
+                "        let g = activation_backward(&z{}, g, |v| {derivative});",
+                l + 1
+            )?;
+        }
+        writeln!(
+            source,
+            "        let g = dense_backward(&LAYER_{l}_WEIGHTS, &g);"
+        )?;
+    }
+    writeln!(source, "        *row = g;")?;
+    writeln!(source, "    }}")?;
+    writeln!(source, "    jacobian")?;
+    writeln!(source, "}}")?;
+    Ok(source)
+}
+
+const DENSE: &str = "
+fn dense<const I: usize, const O: usize>(
+    weights: &[[f64; I]; O],
+    biases: &[f64; O],
+    x: &[f64; I],
+) -> [f64; O] {
+    let mut out = [0.0; O];
+    for ((out, row), bias) in out.iter_mut().zip(weights).zip(biases) {
+        *out = row.iter().zip(x).fold(0.0, |sum, (w, x)| sum + x * w) + bias;
+    }
+    out
+}
+";
+
+const DENSE_BACKWARD: &str = "
+fn dense_backward<const I: usize, const O: usize>(
+    weights: &[[f64; I]; O],
+    grad: &[f64; O],
+) -> [f64; I] {
+    let mut out = [0.0; I];
+    for (row, g) in weights.iter().zip(grad) {
+        for (out, w) in out.iter_mut().zip(row) {
+            *out += w * g;
+        }
+    }
+    out
+}
+";
+
+const ACTIVATION_BACKWARD: &str = "
+fn activation_backward<const N: usize>(
+    z: &[f64; N],
+    grad: [f64; N],
+    derivative: impl Fn(f64) -> f64,
+) -> [f64; N] {
+    let mut out = grad;
+    for (out, z) in out.iter_mut().zip(z) {
+        *out *= derivative(*z);
+    }
+    out
+}
+";
+
+/// Expressions in `v` for an activation and its derivative, `None` for the identity.
+fn activation_source(activation: Activation) -> Option<(String, String)> {
+    let (f, derivative) = match activation {
+        Activation::Identity => return None,
+        Activation::Relu => (
+            "v.max(0.0)".to_string(),
+            "if v > 0.0 { 1.0 } else { 0.0 }".to_string(),
+        ),
+        Activation::Tanh => ("v.tanh()".to_string(), "1.0 - v.tanh().powi(2)".to_string()),
+        Activation::Sigmoid => (
+            "1.0 / (1.0 + (-v).exp())".to_string(),
+            "{ let s = 1.0 / (1.0 + (-v).exp()); s * (1.0 - s) }".to_string(),
+        ),
+        Activation::LeakyRelu(slope) => {
+            let slope = literal(slope);
+            (
+                format!("if v > 0.0 {{ v }} else {{ {slope} * v }}"),
+                format!("if v > 0.0 {{ 1.0 }} else {{ {slope} }}"),
+            )
+        }
+    };
+    Some((f, derivative))
+}
+
+/// The nodes a generated statement reads, leaving out the constant exponent of `Pow` and the
+/// constant slope of `LeakyRelu`.
+fn children<'a>(op: &Option<Operation>, prev: &'a [Value]) -> &'a [Value] {
+    match op {
+        Some(Operation::Pow | Operation::LeakyRelu) => &prev[..1],
+        _ => prev,
+    }
+}
+
+#[allow(clippy::mutable_key_type)]
+fn topo(outputs: &[Value]) -> Vec<Value> {
+    fn visit(v: &Value, order: &mut Vec<Value>, seen: &mut HashSet<Value>) {
+        if seen.insert(v.clone()) {
+            let data = v.borrow();
+            for child in children(&data._op, &data._prev) {
+                visit(child, order, seen);
+            }
+            order.push(v.clone());
+        }
+    }
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    outputs
+        .iter()
+        .for_each(|output| visit(output, &mut order, &mut seen));
+    order
+}
+
+fn literal(value: f64) -> String {
+    if value.is_nan() {
+        "f64::NAN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 {
+            "f64::INFINITY"
+        } else {
+            "f64::NEG_INFINITY"
+        }
+        .to_string()
+    } else {
+        format!("{value:?}")
+    }
+}
+
+fn literals(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(|v| literal(v.borrow().data))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micrograd::Module;
+    use std::{env, fs, process::Command};
+    use uuid::Uuid;
+
+    /// Compiles `source` with a `main` that prints `predict(x)` and `gradient(x)`.
+    fn run_generated(source: &str, x: &[f64]) -> Result<(Vec<f64>, Vec<f64>)> {
+        let dir = env::temp_dir().join(format!("neural_net_codegen_{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+        let program = format!(
+            "{source}\nfn main() {{\n    let x = {x:?};\n    println!(\"{{:?}}\", predict(x));\n    println!(\"{{:?}}\", gradient(x));\n}}\n"
+        );
+        fs::write(dir.join("main.rs"), program)?;
+        let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+        let compiled = Command::new(rustc)
+            .args(["--edition", "2021", "-o"])
+            .arg(dir.join("predict"))
+            .arg(dir.join("main.rs"))
+            .output()?;
+        assert!(
+            compiled.status.success(),
+            "{}",
+            String::from_utf8_lossy(&compiled.stderr)
+        );
+        let run = Command::new(dir.join("predict")).output()?;
+        fs::remove_dir_all(&dir)?;
+        let stdout = String::from_utf8(run.stdout)?;
+        let mut lines = stdout.lines().map(|line| {
+            line.split([',', '[', ']'])
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse::<f64>().expect("generated code prints floats"))
+                .collect::<Vec<f64>>()
+        });
+        let predicted = lines.next().ok_or("missing predict output")?;
+        let gradient = lines.next().ok_or("missing gradient output")?;
+        Ok((predicted, gradient))
+    }
+
+    fn engine_jacobian(
+        build: impl Fn(&[Value]) -> Vec<Value>,
+        x: &[f64],
+        n_out: usize,
+    ) -> Vec<f64> {
+        (0..n_out)
+            .flat_map(|k| {
+                let inputs: Vec<Value> = x.iter().map(|&v| Value::from(v)).collect();
+                build(&inputs)[k].backward();
+                inputs.iter().map(|i| i.borrow().grad).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn assert_close(got: &[f64], want: &[f64]) {
+        assert_eq!(got.len(), want.len());
+        got.iter()
+            .zip(want)
+            .for_each(|(g, w)| assert!((g - w).abs() < 1e-9, "{got:?} vs {want:?}"));
+    }
+
+    #[test]
+    fn generated_mlp_matches_forward() -> Result<()> {
+        let model = MLP::new(vec![2, 8, 8, 2]);
+        let x = [0.5, -1.25];
+        let source = mlp_to_rust(&model, true)?;
+        let (predicted, gradient) = run_generated(&source, &x)?;
+
+        let expected: Vec<f64> = model
+            .forward(&x.map(Value::from))
+            .iter()
+            .map(|o| o.borrow().data)
+            .collect();
+        assert_eq!(predicted, expected);
+        let jacobian = engine_jacobian(|inputs| model.forward(inputs), &x, 2);
+        assert_close(&gradient, &jacobian);
+        Ok(())
+    }
+
+    #[test]
+    fn generated_mlp_without_inputs_compiles() -> Result<()> {
+        let model = MLP::builder(0).layer(2, Activation::Tanh).build();
+        let (predicted, gradient) = run_generated(&mlp_to_rust(&model, true)?, &[])?;
+        assert_eq!(predicted, [0.0, 0.0]);
+        assert!(gradient.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn generated_mlp_keeps_activations() -> Result<()> {
+        let model = MLP::builder(3)
+            .layer(4, Activation::Tanh)
+            .layer(4, Activation::LeakyRelu(0.05))
+            .layer(2, Activation::Sigmoid)
+            .build();
+        let x = [0.25, -0.5, 1.0];
+        let (predicted, gradient) = run_generated(&mlp_to_rust(&model, true)?, &x)?;
+
+        let expected: Vec<f64> = model
+            .forward(&x.map(Value::from))
+            .iter()
+            .map(Value::data)
+            .collect();
+        assert_eq!(predicted, expected);
+        let jacobian = engine_jacobian(|inputs| model.forward(inputs), &x, 2);
+        assert_close(&gradient, &jacobian);
+        Ok(())
+    }
+
+    #[test]
+    fn generated_graph_matches_engine() -> Result<()> {
+        let build = |x: &[Value]| {
+            let (a, b) = (&x[0], &x[1]);
+            vec![
+                (&(a * b) + &a.pow(2.0)).relu(),
+                &(a / b) - &Value::from(3.0),
+                a.max(&(b * &Value::from(-4.0))),
+                &(&a.exp() + &b.abs()) * &a.ln(),
+                &(&a.tanh() + &b.sigmoid()) + &b.leaky_relu(0.1),
+            ]
+        };
+        let x = [1.5, -0.75];
+        let inputs: Vec<Value> = x.iter().map(|&v| Value::from(v)).collect();
+        let outputs = build(&inputs);
+        let source = graph_to_rust(&outputs, &inputs, true)?;
+        let (predicted, gradient) = run_generated(&source, &x)?;
+
+        let expected: Vec<f64> = outputs.iter().map(|o| o.borrow().data).collect();
+        assert_eq!(predicted, expected);
+        assert_close(&gradient, &engine_jacobian(build, &x, 5));
+        Ok(())
+    }
+
+    #[test]
+    fn graph_with_checkpoint_is_rejected() {
+        let a = Value::from(1.0);
+        let out = crate::micrograd::checkpoint(std::slice::from_ref(&a), |x| vec![x[0].relu()]);
+        assert!(graph_to_rust(&out, &[a], false).is_err());
+    }
+}
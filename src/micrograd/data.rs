@@ -0,0 +1,565 @@
+use std::{fmt, fs, io, path::PathBuf};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+/// Samples made of an input row and a target row.
+pub trait Dataset {
+    fn len(&self) -> usize;
+
+    /// Input and target of the sample at `index`.
+    fn get(&self, index: usize) -> (Vec<f64>, Vec<f64>);
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[derive(Debug)]
+pub enum DataError {
+    Io(io::Error),
+    /// A field that is not a number, with its 1-based line and 0-based column.
+    Parse {
+        line: usize,
+        column: usize,
+        value: String,
+    },
+    /// A row with a different number of fields than the first one.
+    ColumnCount {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    /// A selected column that is not in the header, or past the last field.
+    UnknownColumn(String),
+    LengthMismatch {
+        inputs: usize,
+        targets: usize,
+    },
+    InvalidSplit(String),
+}
+
+// region:    --- Error Boilerplate
+
+impl fmt::Display for DataError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataError::Io(err) => write!(f, "reading data failed: {err}"),
+            DataError::Parse {
+                line,
+                column,
+                value,
+            } => write!(f, "line {line}, column {column}: {value:?} is not a number"),
+            DataError::ColumnCount {
+                line,
+                expected,
+                found,
+            } => write!(f, "line {line}: expected {expected} fields, found {found}"),
+            DataError::UnknownColumn(column) => write!(f, "unknown column {column}"),
+            DataError::LengthMismatch { inputs, targets } => {
+                write!(f, "{inputs} inputs but {targets} targets")
+            }
+            DataError::InvalidSplit(reason) => write!(f, "invalid split: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for DataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DataError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DataError {
+    fn from(err: io::Error) -> DataError {
+        DataError::Io(err)
+    }
+}
+
+// endregion: --- Error Boilerplate
+
+/// A dataset held as rows of inputs and targets.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InMemoryDataset {
+    inputs: Vec<Vec<f64>>,
+    targets: Vec<Vec<f64>>,
+}
+
+impl InMemoryDataset {
+    pub fn new(
+        inputs: Vec<Vec<f64>>,
+        targets: Vec<Vec<f64>>,
+    ) -> Result<InMemoryDataset, DataError> {
+        if inputs.len() != targets.len() {
+            return Err(DataError::LengthMismatch {
+                inputs: inputs.len(),
+                targets: targets.len(),
+            });
+        }
+        Ok(InMemoryDataset { inputs, targets })
+    }
+
+    /// Copies the samples at `indices` out of `dataset`, in that order.
+    pub fn from_indices(dataset: &dyn Dataset, indices: &[usize]) -> InMemoryDataset {
+        let (inputs, targets) = indices.iter().map(|&i| dataset.get(i)).unzip();
+        InMemoryDataset { inputs, targets }
+    }
+
+    pub fn inputs(&self) -> &[Vec<f64>] {
+        &self.inputs
+    }
+
+    pub fn targets(&self) -> &[Vec<f64>] {
+        &self.targets
+    }
+}
+
+impl Dataset for InMemoryDataset {
+    fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    fn get(&self, index: usize) -> (Vec<f64>, Vec<f64>) {
+        (self.inputs[index].clone(), self.targets[index].clone())
+    }
+}
+
+/// A column of a CSV file, by header name or by 0-based position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Name(String),
+    Index(usize),
+}
+
+impl From<&str> for Column {
+    fn from(name: &str) -> Column {
+        Column::Name(name.to_string())
+    }
+}
+
+impl From<usize> for Column {
+    fn from(index: usize) -> Column {
+        Column::Index(index)
+    }
+}
+
+/// A dataset read from a CSV file of numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvDataset {
+    columns: Option<Vec<String>>,
+    data: InMemoryDataset,
+}
+
+impl CsvDataset {
+    /// Starts reading `path`, which by default has a header row and its last column as target.
+    pub fn builder(path: impl Into<PathBuf>) -> CsvDatasetBuilder {
+        CsvDatasetBuilder {
+            path: path.into(),
+            has_header: true,
+            delimiter: ',',
+            inputs: None,
+            targets: None,
+        }
+    }
+
+    /// The header row, if the file has one.
+    pub fn columns(&self) -> Option<&[String]> {
+        self.columns.as_deref()
+    }
+
+    pub fn data(&self) -> &InMemoryDataset {
+        &self.data
+    }
+}
+
+impl Dataset for CsvDataset {
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn get(&self, index: usize) -> (Vec<f64>, Vec<f64>) {
+        self.data.get(index)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CsvDatasetBuilder {
+    path: PathBuf,
+    has_header: bool,
+    delimiter: char,
+    inputs: Option<Vec<Column>>,
+    targets: Option<Vec<Column>>,
+}
+
+impl CsvDatasetBuilder {
+    pub fn with_header(mut self, has_header: bool) -> CsvDatasetBuilder {
+        self.has_header = has_header;
+        self
+    }
+
+    pub fn with_delimiter(mut self, delimiter: char) -> CsvDatasetBuilder {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Input columns, every column that is not a target unless set.
+    pub fn with_inputs<C: Into<Column>>(
+        mut self,
+        columns: impl IntoIterator<Item = C>,
+    ) -> CsvDatasetBuilder {
+        self.inputs = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Target columns, the last column unless set.
+    pub fn with_targets<C: Into<Column>>(
+        mut self,
+        columns: impl IntoIterator<Item = C>,
+    ) -> CsvDatasetBuilder {
+        self.targets = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn load(self) -> Result<CsvDataset, DataError> {
+        let text = fs::read_to_string(&self.path)?;
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(n, line)| (n + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let split = |line: &str| -> Vec<String> {
+            line.split(self.delimiter)
+                .map(|field| field.trim().to_string())
+                .collect()
+        };
+
+        let columns = match self.has_header {
+            true => lines.next().map(|(_, line)| split(line)),
+            false => None,
+        };
+        let mut rows: Vec<Vec<f64>> = Vec::new();
+        let mut width = columns.as_ref().map(Vec::len);
+        for (line, text) in lines {
+            let fields = split(text);
+            let expected = *width.get_or_insert(fields.len());
+            if fields.len() != expected {
+                return Err(DataError::ColumnCount {
+                    line,
+                    expected,
+                    found: fields.len(),
+                });
+            }
+            let row = fields
+                .into_iter()
+                .enumerate()
+                .map(|(column, value)| {
+                    value.parse::<f64>().map_err(|_| DataError::Parse {
+                        line,
+                        column,
+                        value,
+                    })
+                })
+                .collect::<Result<Vec<f64>, _>>()?;
+            rows.push(row);
+        }
+
+        let width = width.unwrap_or(0);
+        let resolve = |selected: &[Column]| -> Result<Vec<usize>, DataError> {
+            selected
+                .iter()
+                .map(|column| {
+                    let index = match column {
+                        Column::Index(i) => Some(*i),
+                        Column::Name(name) => columns
+                            .as_ref()
+                            .and_then(|names| names.iter().position(|n| n == name)),
+                    };
+                    index
+                        .filter(|&i| i < width)
+                        .ok_or_else(|| DataError::UnknownColumn(format!("{column:?}")))
+                })
+                .collect()
+        };
+        let targets = match &self.targets {
+            Some(selected) => resolve(selected)?,
+            None if width > 0 => vec![width - 1],
+            None => vec![],
+        };
+        let inputs = match &self.inputs {
+            Some(selected) => resolve(selected)?,
+            None => (0..width).filter(|i| !targets.contains(i)).collect(),
+        };
+        let pick = |row: &[f64], indices: &[usize]| indices.iter().map(|&i| row[i]).collect();
+        let data = InMemoryDataset {
+            inputs: rows.iter().map(|row| pick(row, &inputs)).collect(),
+            targets: rows.iter().map(|row| pick(row, &targets)).collect(),
+        };
+        Ok(CsvDataset { columns, data })
+    }
+}
+
+/// Inputs and targets of a mini-batch, one row each per sample.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Batch {
+    pub inputs: Vec<Vec<f64>>,
+    pub targets: Vec<Vec<f64>>,
+}
+
+/// Splits a dataset into mini-batches, optionally in a fresh random order every epoch.
+pub struct DataLoader<'a> {
+    dataset: &'a dyn Dataset,
+    batch_size: usize,
+    drop_last: bool,
+    pub(crate) rng: Option<ChaCha12Rng>,
+}
+
+impl<'a> DataLoader<'a> {
+    pub fn new(dataset: &'a dyn Dataset, batch_size: usize) -> DataLoader<'a> {
+        assert!(batch_size > 0, "batch size must be positive");
+        DataLoader {
+            dataset,
+            batch_size,
+            drop_last: false,
+            rng: None,
+        }
+    }
+
+    /// Shuffles the samples before every epoch with a generator seeded from `seed`.
+    pub fn with_shuffle(mut self, seed: u64) -> DataLoader<'a> {
+        self.rng = Some(ChaCha12Rng::seed_from_u64(seed));
+        self
+    }
+
+    /// Leaves out the last batch of an epoch if it is smaller than the batch size.
+    pub fn with_drop_last(mut self, drop_last: bool) -> DataLoader<'a> {
+        self.drop_last = drop_last;
+        self
+    }
+
+    /// The batches of one epoch.
+    pub fn epoch(&mut self) -> impl Iterator<Item = Batch> + '_ {
+        let mut order: Vec<usize> = (0..self.dataset.len()).collect();
+        if let Some(rng) = &mut self.rng {
+            order.shuffle(rng);
+        }
+        let mut batches: Vec<Vec<usize>> = order
+            .chunks(self.batch_size)
+            .map(<[usize]>::to_vec)
+            .collect();
+        if self.drop_last && batches.last().is_some_and(|b| b.len() < self.batch_size) {
+            batches.pop();
+        }
+        let dataset = self.dataset;
+        batches.into_iter().map(move |indices| {
+            let (inputs, targets) = indices.iter().map(|&i| dataset.get(i)).unzip();
+            Batch { inputs, targets }
+        })
+    }
+}
+
+/// Splits `dataset` into consecutive parts of the given fractions, plus a last part with
+/// whatever is left, after shuffling with `seed` if there is one.
+pub fn split(
+    dataset: &dyn Dataset,
+    fractions: &[f64],
+    seed: Option<u64>,
+) -> Result<Vec<InMemoryDataset>, DataError> {
+    if let Some(f) = fractions.iter().find(|f| !(0.0..=1.0).contains(*f)) {
+        return Err(DataError::InvalidSplit(format!(
+            "fraction {f} is not in [0, 1]"
+        )));
+    }
+    let total: f64 = fractions.iter().sum();
+    // fractions that add up to 1 on paper can come out just above it, e.g. 0.2 + 0.4 + 0.3 + 0.1
+    if total > 1.0 + 1e-9 {
+        return Err(DataError::InvalidSplit(format!(
+            "fractions add up to {total}"
+        )));
+    }
+    let mut order: Vec<usize> = (0..dataset.len()).collect();
+    if let Some(seed) = seed {
+        order.shuffle(&mut StdRng::seed_from_u64(seed));
+    }
+    let mut rest = order.as_slice();
+    let mut parts = Vec::with_capacity(fractions.len() + 1);
+    for fraction in fractions {
+        let n = ((fraction * dataset.len() as f64).round() as usize).min(rest.len());
+        let (part, remaining) = rest.split_at(n);
+        parts.push(InMemoryDataset::from_indices(dataset, part));
+        rest = remaining;
+    }
+    parts.push(InMemoryDataset::from_indices(dataset, rest));
+    Ok(parts)
+}
+
+/// Holds out `test_fraction` of the shuffled samples, returning `(train, test)`.
+pub fn train_test_split(
+    dataset: &dyn Dataset,
+    test_fraction: f64,
+    seed: u64,
+) -> Result<(InMemoryDataset, InMemoryDataset), DataError> {
+    let mut parts = split(dataset, &[test_fraction], Some(seed))?;
+    let train = parts.pop().expect("split returns the rest");
+    Ok((train, parts.remove(0)))
+}
+
+/// Holds out validation and test fractions of the shuffled samples, returning
+/// `(train, validation, test)`.
+pub fn train_val_test_split(
+    dataset: &dyn Dataset,
+    val_fraction: f64,
+    test_fraction: f64,
+    seed: u64,
+) -> Result<(InMemoryDataset, InMemoryDataset, InMemoryDataset), DataError> {
+    let mut parts = split(dataset, &[val_fraction, test_fraction], Some(seed))?.into_iter();
+    let (val, test, train) = (
+        parts.next().expect("validation part"),
+        parts.next().expect("test part"),
+        parts.next().expect("train part"),
+    );
+    Ok((train, val, test))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use uuid::Uuid;
+
+    fn write_csv(contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("neural_net_data_{}.csv", Uuid::new_v4()));
+        fs::write(&path, contents).expect("temp dir is writable");
+        path
+    }
+
+    fn numbered(n: usize) -> InMemoryDataset {
+        let rows: Vec<Vec<f64>> = (0..n).map(|i| vec![i as f64]).collect();
+        InMemoryDataset::new(rows.clone(), rows).expect("same length")
+    }
+
+    #[test]
+    fn csv_selects_columns() -> crate::Result<()> {
+        let path = write_csv("a,b,label,c\n1,2,1,3\n\n4,5,-1,6\n");
+        let default = CsvDataset::builder(&path).load()?;
+        assert_eq!(default.len(), 2);
+        assert_eq!(default.get(1), (vec![4.0, 5.0, -1.0], vec![6.0]));
+        assert_eq!(default.columns().unwrap()[2], "label");
+
+        let selected = CsvDataset::builder(&path)
+            .with_inputs(["c", "a"])
+            .with_targets(["label"])
+            .load()?;
+        assert_eq!(selected.get(0), (vec![3.0, 1.0], vec![1.0]));
+
+        let by_index = CsvDataset::builder(&path)
+            .with_header(false)
+            .with_inputs([0usize])
+            .with_targets([1usize]);
+        assert!(matches!(
+            by_index.load(),
+            Err(DataError::Parse {
+                line: 1,
+                column: 0,
+                ..
+            })
+        ));
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn csv_reports_malformed_rows() {
+        let path = write_csv("x;y\n1;2\n3\n");
+        let result = CsvDataset::builder(&path).with_delimiter(';').load();
+        assert!(matches!(
+            result,
+            Err(DataError::ColumnCount {
+                line: 3,
+                expected: 2,
+                found: 1
+            })
+        ));
+        fs::remove_file(&path).expect("temp file exists");
+        let missing = CsvDataset::builder(&path).load();
+        assert!(matches!(missing, Err(DataError::Io(_))));
+
+        let path = write_csv("x,y\n1,2\n");
+        let result = CsvDataset::builder(&path).with_targets(["z"]).load();
+        assert!(matches!(result, Err(DataError::UnknownColumn(_))));
+        let result = CsvDataset::builder(&path).with_inputs([2usize]).load();
+        assert!(matches!(result, Err(DataError::UnknownColumn(_))));
+        fs::remove_file(&path).expect("temp file exists");
+
+        assert!(matches!(
+            InMemoryDataset::new(vec![vec![1.0]], vec![]),
+            Err(DataError::LengthMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn loader_batches_and_shuffles() {
+        let dataset = numbered(10);
+        let ids = |batch: &Batch| {
+            batch
+                .inputs
+                .iter()
+                .map(|x| x[0] as usize)
+                .collect::<Vec<_>>()
+        };
+
+        let mut loader = DataLoader::new(&dataset, 4);
+        let sizes: Vec<usize> = loader.epoch().map(|b| b.inputs.len()).collect();
+        assert_eq!(sizes, [4, 4, 2]);
+        let mut loader = DataLoader::new(&dataset, 4).with_drop_last(true);
+        assert_eq!(loader.epoch().count(), 2);
+
+        let epochs = |seed| {
+            let mut loader = DataLoader::new(&dataset, 3).with_shuffle(seed);
+            (0..2)
+                .map(|_| loader.epoch().flat_map(|b| ids(&b)).collect::<Vec<_>>())
+                .collect::<Vec<_>>()
+        };
+        let shuffled = epochs(5);
+        assert_eq!(shuffled, epochs(5));
+        assert_ne!(shuffled[0], shuffled[1]);
+        let mut sorted = shuffled[0].clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..10).collect::<Vec<_>>());
+        for batch in DataLoader::new(&dataset, 3).with_shuffle(1).epoch() {
+            assert_eq!(batch.inputs, batch.targets);
+        }
+    }
+
+    #[test]
+    fn splits_partition_the_samples() -> crate::Result<()> {
+        let dataset = numbered(20);
+        let (train, val, test) = train_val_test_split(&dataset, 0.2, 0.1, 3)?;
+        assert_eq!((train.len(), val.len(), test.len()), (14, 4, 2));
+        let mut all: Vec<f64> = [train.inputs(), val.inputs(), test.inputs()]
+            .concat()
+            .into_iter()
+            .flatten()
+            .collect();
+        all.sort_by(f64::total_cmp);
+        assert_eq!(all, (0..20).map(|i| i as f64).collect::<Vec<_>>());
+
+        let (train, test) = train_test_split(&dataset, 0.25, 3)?;
+        assert_eq!((train.len(), test.len()), (15, 5));
+        let ordered = split(&dataset, &[0.5], None)?;
+        assert_eq!(ordered[0].inputs()[..2], [vec![0.0], vec![1.0]]);
+        let sizes = |fractions: &[f64]| -> crate::Result<Vec<usize>> {
+            let parts = split(&dataset, fractions, None)?;
+            Ok(parts.iter().map(InMemoryDataset::len).collect())
+        };
+        assert_eq!(sizes(&[0.1, 0.2, 0.7])?, [2, 4, 14, 0]);
+        assert_eq!(sizes(&[0.2, 0.4, 0.3, 0.1])?, [4, 8, 6, 2, 0]);
+        assert!(matches!(
+            split(&dataset, &[0.7, 0.6], None),
+            Err(DataError::InvalidSplit(_))
+        ));
+        Ok(())
+    }
+}
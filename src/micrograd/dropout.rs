@@ -0,0 +1,113 @@
+use std::{cell::RefCell, rc::Rc};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+use super::{Buffer, Module, Value};
+
+/// Zeroes each value with probability `p` while training.
+///
+/// Kept values are scaled by `1 / (1 - p)` so their expected value is unchanged, which lets
+/// evaluation pass the input through untouched. Like every module it starts in evaluation
+/// mode; call [`Module::train`] before fitting.
+#[derive(Debug)]
+pub struct Dropout {
+    p: f64,
+    rng: Rc<RefCell<ChaCha12Rng>>,
+    training: bool,
+}
+
+impl Dropout {
+    pub fn new(p: f64) -> Dropout {
+        assert!(
+            (0.0..1.0).contains(&p),
+            "dropout probability must be in [0, 1), got {p}"
+        );
+        Dropout {
+            p,
+            rng: Rc::new(RefCell::new(ChaCha12Rng::from_entropy())),
+            training: false,
+        }
+    }
+
+    /// Draws the dropped values from a generator seeded with `seed`, so masks repeat exactly.
+    pub fn with_seed(self, seed: u64) -> Dropout {
+        self.rng.replace(ChaCha12Rng::seed_from_u64(seed));
+        self
+    }
+
+    pub fn p(&self) -> f64 {
+        self.p
+    }
+}
+
+impl Module for Dropout {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        if !self.training || self.p == 0.0 {
+            return input.to_vec();
+        }
+        let mut rng = self.rng.borrow_mut();
+        let kept = Value::from(1.0 / (1.0 - self.p));
+        let dropped = Value::from(0.0);
+        input
+            .iter()
+            .map(|x| {
+                let mask = if rng.gen::<f64>() < self.p {
+                    &dropped
+                } else {
+                    &kept
+                };
+                x * mask
+            })
+            .collect()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        Vec::new()
+    }
+
+    fn named_buffers(&self) -> Vec<(String, Buffer)> {
+        vec![("rng".to_string(), Buffer::Rng(self.rng.clone()))]
+    }
+
+    fn set_training(&mut self, training: bool) {
+        self.training = training;
+    }
+
+    fn is_training(&self) -> bool {
+        self.training
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dropout_scales_kept_values() {
+        let mut dropout = Dropout::new(0.3).with_seed(5);
+        let input = vec![Value::from(2.0); 10_000];
+        assert_eq!(dropout.forward(&input), input);
+
+        dropout.train();
+        let output: Vec<f64> = dropout.forward(&input).iter().map(Value::data).collect();
+        let zeros = output.iter().filter(|&&x| x == 0.0).count() as f64 / output.len() as f64;
+        assert!((zeros - 0.3).abs() < 0.02, "dropped fraction {zeros}");
+        let kept = 2.0 / 0.7;
+        assert!(output.iter().all(|&x| x == 0.0 || (x - kept).abs() < 1e-12));
+        let mean = output.iter().sum::<f64>() / output.len() as f64;
+        assert!((mean - 2.0).abs() < 0.05, "mean {mean}");
+
+        let mut repeat = Dropout::new(0.3).with_seed(5);
+        repeat.train();
+        let again: Vec<f64> = repeat.forward(&input).iter().map(Value::data).collect();
+        assert_eq!(again, output);
+
+        let x = Value::from(1.0);
+        let y = dropout.forward(&vec![x.clone(); 100]);
+        let sum: Value = y.iter().cloned().sum();
+        sum.backward();
+        let expected = y.iter().filter(|v| v.data() != 0.0).count() as f64 * kept / 2.0;
+        assert!((x.grad() - expected).abs() < 1e-9);
+    }
+}
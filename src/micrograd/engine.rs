@@ -1,5 +1,6 @@
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
+    cmp::Ordering,
     collections::HashSet,
     fmt,
     hash::{Hash, Hasher},
@@ -9,9 +10,11 @@ use std::{
 };
 use uuid::Uuid;
 
+use super::Segment;
+
 // #[derive(Default)]
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Operation {
     Add,
     Sub,
@@ -19,6 +22,17 @@ pub enum Operation {
     Div,
     Pow,
     Relu,
+    Max,
+    Exp,
+    Log,
+    Abs,
+    Tanh,
+    Sigmoid,
+    LeakyRelu,
+    /// The segment node of a [`checkpoint`](super::checkpoint()).
+    Checkpoint,
+    /// The output of a checkpointed segment with this index.
+    CheckpointOutput(usize),
     // #[default]
     // None,
 }
@@ -26,10 +40,12 @@ pub enum Operation {
 pub struct ValueData {
     pub data: f64,
     pub grad: f64,
+    pub label: Option<String>,
     pub uuid: Uuid,
     pub _backward: Option<fn(value: &ValueData)>,
     pub _prev: Vec<Value>,
     pub _op: Option<Operation>,
+    pub _segment: Option<Rc<Segment>>,
 }
 
 #[derive(Clone, Debug)]
@@ -42,19 +58,39 @@ impl ops::Deref for Value {
     }
 }
 
+thread_local! {
+    static LIVE_VALUES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Number of `ValueData` nodes currently alive on this thread.
+pub fn live_values() -> usize {
+    LIVE_VALUES.with(|count| count.get())
+}
+
 impl ValueData {
     fn new(data: f64) -> ValueData {
+        LIVE_VALUES.with(|count| count.set(count.get() + 1));
         ValueData {
             data,
             grad: 0.0,
+            label: None,
             uuid: Uuid::new_v4(),
             _backward: None,
             _prev: Vec::new(),
             _op: None,
+            _segment: None,
         }
     }
 }
 
+impl Drop for ValueData {
+    fn drop(&mut self) {
+        LIVE_VALUES.with(|count| count.set(count.get().saturating_sub(1)));
+    }
+}
+
+/// Hashes by the uuid, which never changes, so `Value` is a sound key for the sets and maps
+/// that walk a graph even though clippy flags its interior mutability.
 impl Hash for Value {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.borrow().uuid.hash(state);
@@ -80,6 +116,12 @@ impl Value {
         Value(Rc::new(RefCell::new(value)))
     }
 
+    /// Names this value, used in place of its data when rendering expressions and graphs.
+    pub fn with_label(self, label: &str) -> Value {
+        self.borrow_mut().label = Some(label.to_string());
+        self
+    }
+
     pub fn pow(&self, power: f64) -> Value {
         let result = Value::from(self.borrow().data.powf(power));
         result.borrow_mut()._op = Some(Operation::Pow);
@@ -95,18 +137,11 @@ impl Value {
     }
 
     pub fn backward(&self) {
-        let mut topo: Vec<Value> = vec![];
-        let mut visited: HashSet<Value> = HashSet::new();
-        self.build_topo(&mut topo, &mut visited);
-        self.borrow_mut().grad = 1.0;
-        topo.reverse();
-        topo.iter().for_each(|v| {
-            if let Some(backprop) = v.borrow()._backward {
-                backprop(&v.borrow())
-            }
-        });
+        self.borrow_mut().grad = 0.0;
+        backward_from(&[(self.clone(), 1.0)]);
     }
 
+    #[allow(clippy::mutable_key_type)]
     fn build_topo(&self, topo: &mut Vec<Value>, visited: &mut HashSet<Value>) {
         if visited.insert(self.clone()) {
             self.borrow()
@@ -128,6 +163,190 @@ impl Value {
 
         result
     }
+
+    /// Like `relu`, but scales negative inputs by `slope` instead of zeroing them.
+    pub fn leaky_relu(&self, slope: f64) -> Value {
+        let x = self.borrow().data;
+        let result = Value::from(if x > 0.0 { x } else { slope * x });
+        result.borrow_mut()._op = Some(Operation::LeakyRelu);
+        result.borrow_mut()._prev = vec![self.clone(), Value::from(slope)];
+        result.borrow_mut()._backward = Some(|val: &ValueData| {
+            let x = val._prev[0].borrow().data;
+            let slope = val._prev[1].borrow().data;
+            val._prev[0].borrow_mut().grad += if x > 0.0 { val.grad } else { slope * val.grad };
+        });
+
+        result
+    }
+
+    pub fn tanh(&self) -> Value {
+        let result = Value::from(self.borrow().data.tanh());
+        result.borrow_mut()._op = Some(Operation::Tanh);
+        result.borrow_mut()._prev = vec![self.clone()];
+        result.borrow_mut()._backward = Some(|val: &ValueData| {
+            val._prev[0].borrow_mut().grad += (1.0 - val.data * val.data) * val.grad;
+        });
+
+        result
+    }
+
+    /// The logistic function `1 / (1 + exp(-x))`.
+    pub fn sigmoid(&self) -> Value {
+        let result = Value::from(1.0 / (1.0 + (-self.borrow().data).exp()));
+        result.borrow_mut()._op = Some(Operation::Sigmoid);
+        result.borrow_mut()._prev = vec![self.clone()];
+        result.borrow_mut()._backward = Some(|val: &ValueData| {
+            val._prev[0].borrow_mut().grad += val.data * (1.0 - val.data) * val.grad;
+        });
+
+        result
+    }
+
+    /// The larger of the two values, passing the gradient to whichever won (`self` on ties).
+    pub fn max(&self, other: &Value) -> Value {
+        let result = Value::from(self.borrow().data.max(other.borrow().data));
+        result.borrow_mut()._op = Some(Operation::Max);
+        result.borrow_mut()._prev = vec![self.clone(), other.clone()];
+        result.borrow_mut()._backward = Some(|val: &ValueData| {
+            let winner = if val._prev[0].borrow().data >= val._prev[1].borrow().data {
+                0
+            } else {
+                1
+            };
+            val._prev[winner].borrow_mut().grad += val.grad;
+        });
+
+        result
+    }
+
+    pub fn exp(&self) -> Value {
+        let result = Value::from(self.borrow().data.exp());
+        result.borrow_mut()._op = Some(Operation::Exp);
+        result.borrow_mut()._prev = vec![self.clone()];
+        result.borrow_mut()._backward = Some(|val: &ValueData| {
+            val._prev[0].borrow_mut().grad += val.data * val.grad;
+        });
+
+        result
+    }
+
+    /// Natural logarithm.
+    pub fn ln(&self) -> Value {
+        let result = Value::from(self.borrow().data.ln());
+        result.borrow_mut()._op = Some(Operation::Log);
+        result.borrow_mut()._prev = vec![self.clone()];
+        result.borrow_mut()._backward = Some(|val: &ValueData| {
+            let x = val._prev[0].borrow().data;
+            val._prev[0].borrow_mut().grad += val.grad / x;
+        });
+
+        result
+    }
+
+    /// Absolute value, with a zero gradient at zero.
+    pub fn abs(&self) -> Value {
+        let result = Value::from(self.borrow().data.abs());
+        result.borrow_mut()._op = Some(Operation::Abs);
+        result.borrow_mut()._prev = vec![self.clone()];
+        result.borrow_mut()._backward = Some(|val: &ValueData| {
+            let x = val._prev[0].borrow().data;
+            let sign = if x > 0.0 {
+                1.0
+            } else if x < 0.0 {
+                -1.0
+            } else {
+                0.0
+            };
+            val._prev[0].borrow_mut().grad += sign * val.grad;
+        });
+
+        result
+    }
+
+    pub fn data(&self) -> f64 {
+        self.borrow().data
+    }
+
+    pub fn grad(&self) -> f64 {
+        self.borrow().grad
+    }
+
+    /// Compares by data, unlike `==` which compares node identity.
+    pub fn eq_data(&self, other: &Value) -> bool {
+        self.data() == other.data()
+    }
+
+    /// Orders by data using `f64::total_cmp`, e.g. for `values.sort_by(Value::cmp_data)`.
+    pub fn cmp_data(&self, other: &Value) -> Ordering {
+        self.data().total_cmp(&other.data())
+    }
+}
+
+/// Index of the largest value by data, the first one on ties.
+pub fn argmax(values: &[Value]) -> Option<usize> {
+    values
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by(|(_, a), (_, b)| a.cmp_data(b))
+        .map(|(i, _)| i)
+}
+
+/// Index of the smallest value by data, the first one on ties.
+pub fn argmin(values: &[Value]) -> Option<usize> {
+    values
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.cmp_data(b))
+        .map(|(i, _)| i)
+}
+
+/// Differentiable maximum of `values`; the gradient flows to the first largest one.
+pub fn reduce_max(values: &[Value]) -> Value {
+    let (first, rest) = values.split_first().expect("must have a value");
+    rest.iter().fold(first.clone(), |acc, val| acc.max(val))
+}
+
+/// `ln(sum(exp(values)))`, shifted by the largest value so it cannot overflow.
+pub fn log_sum_exp(values: &[Value]) -> Value {
+    let shift = Value::from(
+        values
+            .iter()
+            .map(Value::data)
+            .fold(f64::NEG_INFINITY, f64::max),
+    );
+    let sum: Value = values.iter().map(|v| (v - &shift).exp()).sum();
+    &sum.ln() + &shift
+}
+
+/// `values` minus their `log_sum_exp`, the log-probabilities of a softmax.
+pub fn log_softmax(values: &[Value]) -> Vec<Value> {
+    let lse = log_sum_exp(values);
+    values.iter().map(|v| v - &lse).collect()
+}
+
+/// Normalises `values` into probabilities.
+pub fn softmax(values: &[Value]) -> Vec<Value> {
+    log_softmax(values).iter().map(Value::exp).collect()
+}
+
+/// Backpropagates from several roots at once, adding each seed to its root's gradient.
+#[allow(clippy::mutable_key_type)]
+pub(crate) fn backward_from(roots: &[(Value, f64)]) {
+    let mut topo: Vec<Value> = vec![];
+    let mut visited: HashSet<Value> = HashSet::new();
+    for (root, _) in roots {
+        root.build_topo(&mut topo, &mut visited);
+    }
+    for (root, seed) in roots {
+        root.borrow_mut().grad += *seed;
+    }
+    topo.reverse();
+    topo.iter().for_each(|v| {
+        if let Some(backprop) = v.borrow()._backward {
+            backprop(&v.borrow())
+        }
+    });
 }
 
 impl<T: Into<f64>> From<T> for Value {
@@ -154,6 +373,7 @@ impl ops::Add<&Value> for &Value {
 
 impl ops::Mul<&Value> for &Value {
     type Output = Value;
+    #[allow(clippy::suspicious_arithmetic_impl)]
     fn mul(self, _rhs: &Value) -> Value {
         let result = Value::from(self.borrow().data * _rhs.borrow().data);
         result.borrow_mut()._op = Some(Operation::Mul);
@@ -172,25 +392,20 @@ impl ops::Mul<&Value> for &Value {
 impl ops::Div<&Value> for &Value {
     type Output = Value;
     fn div(self, _rhs: &Value) -> Value {
-        let result = self * &_rhs.pow(-1.0);
-
-        result
+        self * &_rhs.pow(-1.0)
     }
 }
 
 impl ops::Neg for &Value {
     type Output = Value;
     fn neg(self) -> Value {
-        let result = self * &Value::from(-1.0);
-
-        result
+        self * &Value::from(-1.0)
     }
 }
 impl ops::Sub for &Value {
     type Output = Value;
     fn sub(self, _rhs: &Value) -> Value {
-        let result = &-_rhs + self;
-        result
+        &-_rhs + self
     }
 }
 impl ops::AddAssign<&Value> for Value {
@@ -323,4 +538,63 @@ mod tests {
         assert_eq!(b.relu().borrow().data, 0.0);
         assert_eq!(c.relu().borrow().data, 0.0);
     }
+    #[test]
+    fn sanity_check_compare_data() {
+        let a = Value::from(2.0);
+        let b = Value::from(2.0);
+        let c = Value::from(-1.0);
+        assert!(a != b);
+        assert!(a.eq_data(&b));
+        assert_eq!(c.cmp_data(&a), Ordering::Less);
+        let mut sorted = vec![a.clone(), c.clone(), b.clone()];
+        sorted.sort_by(Value::cmp_data);
+        assert_eq!(sorted, vec![c, a, b]);
+    }
+    #[test]
+    fn sanity_check_argmax_argmin() {
+        let values: Vec<Value> = [1.0, 5.0, -3.0, 5.0, -3.0].map(Value::from).to_vec();
+        assert_eq!(argmax(&values), Some(1));
+        assert_eq!(argmin(&values), Some(2));
+        assert_eq!(argmax(&[]), None);
+    }
+    #[test]
+    fn sanity_check_backprop_max() {
+        let values: Vec<Value> = [1.0, 4.0, 4.0, -2.0].map(Value::from).to_vec();
+        let m = reduce_max(&values);
+        let out = &m * &Value::from(3.0);
+        out.backward();
+        assert_eq!(m.borrow().data, 4.0);
+        let grads: Vec<f64> = values.iter().map(Value::grad).collect();
+        assert_eq!(grads, vec![0.0, 3.0, 0.0, 0.0]);
+    }
+    #[test]
+    fn sanity_check_backprop_exp_ln_abs() {
+        let a = Value::from(2.0);
+        let b = Value::from(-3.0);
+        let out = &(&a.exp() + &a.ln()) + &b.abs();
+        out.backward();
+        assert_eq!(out.borrow().data, 2.0f64.exp() + 2.0f64.ln() + 3.0);
+        assert_eq!(a.grad(), 2.0f64.exp() + 0.5);
+        assert_eq!(b.grad(), -1.0);
+    }
+    #[test]
+    fn sanity_check_softmax() {
+        let values: Vec<Value> = [1000.0, 1000.0, 0.0].map(Value::from).to_vec();
+        let probs: Vec<f64> = softmax(&values).iter().map(Value::data).collect();
+        assert!((probs[0] - 0.5).abs() < 1e-12);
+        assert!((probs[1] - 0.5).abs() < 1e-12);
+        assert_eq!(probs[2], 0.0);
+        assert!((log_sum_exp(&values).data() - (1000.0 + 2.0f64.ln())).abs() < 1e-9);
+    }
+    #[test]
+    fn sanity_check_backprop_activations() {
+        let a = Value::from(0.5);
+        let b = Value::from(-2.0);
+        let out = &(&a.tanh() + &a.sigmoid()) + &b.leaky_relu(0.1);
+        out.backward();
+        let (t, s) = (0.5f64.tanh(), 1.0 / (1.0 + (-0.5f64).exp()));
+        assert_eq!(out.borrow().data, t + s - 0.2);
+        assert!((a.grad() - (1.0 - t * t + s * (1.0 - s))).abs() < 1e-12);
+        assert_eq!(b.grad(), 0.1);
+    }
 }
@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet};
+
+use uuid::Uuid;
+
+use super::{Operation, Value};
+
+// binding strength of each kind of expression, weakest first
+const ADD: u8 = 1;
+const MUL: u8 = 2;
+const NEG: u8 = 3;
+const POW: u8 = 4;
+const ATOM: u8 = 5;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Style {
+    Plain,
+    Latex,
+}
+
+/// Rendering state: which nodes are used more than once, and the temporaries bound so far.
+struct Context {
+    style: Style,
+    uses: HashMap<Uuid, usize>,
+    names: HashMap<Uuid, String>,
+    bindings: Vec<String>,
+}
+
+impl Context {
+    fn new(root: &Value, style: Style) -> Context {
+        let mut uses = HashMap::new();
+        let mut seen = HashSet::new();
+        let mut stack = vec![root.clone()];
+        while let Some(v) = stack.pop() {
+            if seen.insert(v.borrow().uuid) {
+                for child in &v.borrow()._prev {
+                    *uses.entry(child.borrow().uuid).or_insert(0) += 1;
+                    stack.push(child.clone());
+                }
+            }
+        }
+        Context {
+            style,
+            uses,
+            names: HashMap::new(),
+            bindings: Vec::new(),
+        }
+    }
+
+    /// The bindings, one per line, followed by the expression itself.
+    fn finish(self, expr: String) -> String {
+        let separator = match self.style {
+            Style::Plain => "\n",
+            Style::Latex => " \\\\\n",
+        };
+        self.bindings
+            .into_iter()
+            .chain([expr])
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+}
+
+impl Value {
+    /// Renders the computation behind this value as an infix expression, e.g. `(a + b) * c`.
+    ///
+    /// Leaves are shown by their label if they have one, otherwise by their data. The
+    /// `-x`, `a - b` and `a / b` patterns built by the operators are shown as written.
+    /// Subexpressions used more than once are bound to temporaries `t0`, `t1`, ... on lines
+    /// of their own, e.g. `t0 = a + b` then `t0 * t0`, so shared graphs render in linear size.
+    pub fn to_expr(&self) -> String {
+        let mut cx = Context::new(self, Style::Plain);
+        let (expr, _) = render(self, &mut cx);
+        cx.finish(expr)
+    }
+
+    /// Renders the computation behind this value as LaTeX, e.g. `\frac{a}{b}`, with shared
+    /// subexpressions bound to temporaries `t_{0}`, `t_{1}`, ... as in [`Value::to_expr`].
+    pub fn to_latex(&self) -> String {
+        let mut cx = Context::new(self, Style::Latex);
+        let (expr, _) = render(self, &mut cx);
+        cx.finish(expr)
+    }
+}
+
+/// Renders `v`, or the temporary it is bound to if it is an operation used more than once.
+fn render(v: &Value, cx: &mut Context) -> (String, u8) {
+    let (uuid, shared) = {
+        let data = v.borrow();
+        let uses = cx.uses.get(&data.uuid).copied().unwrap_or(0);
+        (data.uuid, data._op.is_some() && uses > 1)
+    };
+    if !shared {
+        return render_node(v, cx);
+    }
+    if let Some(name) = cx.names.get(&uuid) {
+        return (name.clone(), ATOM);
+    }
+    let (expr, _) = render_node(v, cx);
+    let n = cx.names.len();
+    let name = match cx.style {
+        Style::Plain => format!("t{n}"),
+        Style::Latex => format!("t_{{{n}}}"),
+    };
+    cx.bindings.push(format!("{name} = {expr}"));
+    cx.names.insert(uuid, name.clone());
+    (name, ATOM)
+}
+
+fn render_node(v: &Value, cx: &mut Context) -> (String, u8) {
+    if let Some(x) = negated(v) {
+        return (format!("-{}", operand(&x, NEG, true, cx)), NEG);
+    }
+    let data = v.borrow();
+    let prev = &data._prev;
+    match data._op {
+        None => match &data.label {
+            Some(label) => (label.clone(), ATOM),
+            None => number(data.data),
+        },
+        Some(Operation::Add) => match (negated(&prev[0]), negated(&prev[1])) {
+            (_, Some(b)) => infix(&prev[0], " - ", &b, ADD, true, cx),
+            (Some(a), None) => infix(&prev[1], " - ", &a, ADD, true, cx),
+            (None, None) => infix(&prev[0], " + ", &prev[1], ADD, false, cx),
+        },
+        Some(Operation::Sub) => infix(&prev[0], " - ", &prev[1], ADD, true, cx),
+        Some(Operation::Mul) => match reciprocal(&prev[1]) {
+            Some(b) => divide(&prev[0], &b, cx),
+            None => {
+                let op = match cx.style {
+                    Style::Plain => " * ",
+                    Style::Latex => " \\cdot ",
+                };
+                infix(&prev[0], op, &prev[1], MUL, false, cx)
+            }
+        },
+        Some(Operation::Div) => divide(&prev[0], &prev[1], cx),
+        Some(Operation::Pow) => {
+            let base = operand(&prev[0], POW, true, cx);
+            let (exponent, precedence) = number(prev[1].borrow().data);
+            let expr = match cx.style {
+                Style::Plain if precedence < ATOM => format!("{base}^({exponent})"),
+                Style::Plain => format!("{base}^{exponent}"),
+                Style::Latex => format!("{base}^{{{exponent}}}"),
+            };
+            (expr, POW)
+        }
+        Some(Operation::Relu) => function("relu", "\\operatorname{ReLU}", &prev[..1], cx),
+        Some(Operation::Max) => function("max", "\\max", prev, cx),
+        Some(Operation::Exp) => function("exp", "\\exp", prev, cx),
+        Some(Operation::Log) => function("ln", "\\ln", prev, cx),
+        Some(Operation::Tanh) => function("tanh", "\\tanh", prev, cx),
+        Some(Operation::Sigmoid) => function("sigmoid", "\\sigma", prev, cx),
+        Some(Operation::LeakyRelu) => function("leaky_relu", "\\operatorname{LeakyReLU}", prev, cx),
+        Some(Operation::Abs) => match cx.style {
+            Style::Plain => function("abs", "", prev, cx),
+            Style::Latex => (format!("\\left|{}\\right|", render(&prev[0], cx).0), ATOM),
+        },
+        Some(Operation::Checkpoint) => {
+            function("checkpoint", "\\operatorname{checkpoint}", prev, cx)
+        }
+        Some(Operation::CheckpointOutput(index)) => {
+            let (segment, _) = render(&prev[0], cx);
+            (format!("{segment}[{index}]"), ATOM)
+        }
+    }
+}
+
+fn number(data: f64) -> (String, u8) {
+    (format!("{data}"), if data < 0.0 { NEG } else { ATOM })
+}
+
+/// Renders `v`, wrapped in parentheses if it binds looser than `precedence` allows.
+fn operand(v: &Value, precedence: u8, strict: bool, cx: &mut Context) -> String {
+    let (expr, own) = render(v, cx);
+    if own < precedence || (strict && own == precedence) {
+        match cx.style {
+            Style::Plain => format!("({expr})"),
+            Style::Latex => format!("\\left({expr}\\right)"),
+        }
+    } else {
+        expr
+    }
+}
+
+fn infix(
+    a: &Value,
+    op: &str,
+    b: &Value,
+    precedence: u8,
+    strict: bool,
+    cx: &mut Context,
+) -> (String, u8) {
+    let left = operand(a, precedence, false, cx);
+    let right = operand(b, precedence, strict, cx);
+    (format!("{left}{op}{right}"), precedence)
+}
+
+fn divide(a: &Value, b: &Value, cx: &mut Context) -> (String, u8) {
+    match cx.style {
+        Style::Plain => infix(a, " / ", b, MUL, true, cx),
+        Style::Latex => (
+            format!("\\frac{{{}}}{{{}}}", render(a, cx).0, render(b, cx).0),
+            MUL,
+        ),
+    }
+}
+
+fn function(plain: &str, latex: &str, args: &[Value], cx: &mut Context) -> (String, u8) {
+    let args = args
+        .iter()
+        .map(|arg| render(arg, cx).0)
+        .collect::<Vec<_>>()
+        .join(", ");
+    match cx.style {
+        Style::Plain => (format!("{plain}({args})"), ATOM),
+        Style::Latex => (format!("{latex}\\left({args}\\right)"), ATOM),
+    }
+}
+
+/// The `x` in `x * -1`, which is how `-x` is built.
+fn negated(v: &Value) -> Option<Value> {
+    let data = v.borrow();
+    if data._op != Some(Operation::Mul) {
+        return None;
+    }
+    let is_minus_one = |c: &Value| {
+        let c = c.borrow();
+        c._op.is_none() && c.label.is_none() && c.data == -1.0
+    };
+    if is_minus_one(&data._prev[1]) {
+        Some(data._prev[0].clone())
+    } else if is_minus_one(&data._prev[0]) {
+        Some(data._prev[1].clone())
+    } else {
+        None
+    }
+}
+
+/// The `x` in `x.pow(-1)`, which is how the divisor of `a / x` is built.
+fn reciprocal(v: &Value) -> Option<Value> {
+    let data = v.borrow();
+    if data._op == Some(Operation::Pow) && data._prev[1].borrow().data == -1.0 {
+        Some(data._prev[0].clone())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves() -> (Value, Value, Value) {
+        (
+            Value::from(1.0).with_label("a"),
+            Value::from(2.0).with_label("b"),
+            Value::from(3.0).with_label("c"),
+        )
+    }
+
+    #[test]
+    fn expr_minimal_parentheses() {
+        let (a, b, c) = leaves();
+        assert_eq!((&(&a * &b) + &c).to_expr(), "a * b + c");
+        assert_eq!((&(&a + &b) * &c).to_expr(), "(a + b) * c");
+        assert_eq!((&a * &(&b * &c)).to_expr(), "a * b * c");
+        assert_eq!((&(&a - &b) - &c).to_expr(), "a - b - c");
+        assert_eq!((&a - &(&b - &c)).to_expr(), "a - (b - c)");
+        assert_eq!((&a / &(&b * &c)).to_expr(), "a / (b * c)");
+        assert_eq!((-&(&a + &b)).to_expr(), "-(a + b)");
+        assert_eq!((&a + &b).pow(2.0).to_expr(), "(a + b)^2");
+        assert_eq!(a.pow(-0.5).to_expr(), "a^(-0.5)");
+    }
+
+    #[test]
+    fn expr_unlabeled_leaves_and_functions() {
+        let (a, b, _) = leaves();
+        let expr = &(&a * &Value::from(2.0)) + &Value::from(1.5);
+        assert_eq!(expr.to_expr(), "a * 2 + 1.5");
+        assert_eq!((&a * &b).relu().to_expr(), "relu(a * b)");
+        assert_eq!(a.max(&-&b).to_expr(), "max(a, -b)");
+        assert_eq!(Value::from(-2.0).pow(2.0).to_expr(), "(-2)^2");
+    }
+
+    #[test]
+    fn latex_rendering() {
+        let (a, b, c) = leaves();
+        assert_eq!(
+            (&(&a + &b) * &c).to_latex(),
+            "\\left(a + b\\right) \\cdot c"
+        );
+        assert_eq!((&a / &b).to_latex(), "\\frac{a}{b}");
+        assert_eq!((&a - &b).pow(2.0).to_latex(), "\\left(a - b\\right)^{2}");
+        assert_eq!(a.relu().to_latex(), "\\operatorname{ReLU}\\left(a\\right)");
+    }
+
+    #[test]
+    fn shared_subexpressions_are_bound_once() {
+        let (a, b, _) = leaves();
+        let sum = &a + &b;
+        assert_eq!((&sum * &sum).to_expr(), "t0 = a + b\nt0 * t0");
+        assert_eq!(
+            (&sum / &sum.relu()).to_latex(),
+            "t_{0} = a + b \\\\\n\\frac{t_{0}}{\\operatorname{ReLU}\\left(t_{0}\\right)}"
+        );
+
+        // without temporaries this would print 2^30 copies of x
+        let mut x = Value::from(1.0).with_label("x");
+        for _ in 0..30 {
+            x = &x + &x;
+        }
+        let expr = x.to_expr();
+        assert_eq!(expr.lines().count(), 30);
+        assert!(expr.len() < 600, "{expr}");
+        assert!(expr.starts_with("t0 = x + x\nt1 = t0 + t0\n"));
+        assert!(expr.ends_with("t28 + t28"));
+    }
+}
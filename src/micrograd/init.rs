@@ -0,0 +1,182 @@
+use std::f64::consts::PI;
+
+use rand::{distributions::Uniform, Rng};
+
+/// How the weights of a layer are drawn. Biases always start at zero.
+///
+/// The fan-in scaled schemes keep the variance of activations roughly constant from layer to
+/// layer, which deeper networks need to train at all.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Init {
+    /// Uniform on `[low, high]`.
+    Uniform(f64, f64),
+    /// Normal with the given mean and standard deviation.
+    Normal(f64, f64),
+    /// Glorot uniform, variance `2 / (nin + nout)`.
+    XavierUniform,
+    /// Glorot normal, variance `2 / (nin + nout)`.
+    XavierNormal,
+    /// Kaiming uniform for ReLU layers, variance `2 / nin`.
+    HeUniform,
+    /// Kaiming normal for ReLU layers, variance `2 / nin`.
+    HeNormal,
+    /// A (semi-)orthogonal matrix scaled by the given gain.
+    Orthogonal(f64),
+    Constant(f64),
+}
+
+impl Default for Init {
+    fn default() -> Init {
+        Init::Uniform(-1.0, 1.0)
+    }
+}
+
+impl Init {
+    /// Draws the `nout` rows of `nin` weights of a layer.
+    pub fn weights(&self, nin: usize, nout: usize, rng: &mut impl Rng) -> Vec<Vec<f64>> {
+        let fan_avg = (nin + nout) as f64 / 2.0;
+        match *self {
+            Init::Uniform(low, high) => {
+                let uniform = Uniform::new_inclusive(low, high);
+                matrix(nin, nout, || rng.sample(uniform))
+            }
+            Init::Normal(mean, std) => matrix(nin, nout, || mean + std * standard_normal(rng)),
+            Init::XavierUniform => {
+                Init::uniform_with_variance(1.0 / fan_avg).weights(nin, nout, rng)
+            }
+            Init::XavierNormal => Init::Normal(0.0, (1.0 / fan_avg).sqrt()).weights(nin, nout, rng),
+            Init::HeUniform => {
+                Init::uniform_with_variance(2.0 / nin as f64).weights(nin, nout, rng)
+            }
+            Init::HeNormal => Init::Normal(0.0, (2.0 / nin as f64).sqrt()).weights(nin, nout, rng),
+            Init::Orthogonal(gain) => orthogonal(nin, nout, gain, rng),
+            Init::Constant(c) => matrix(nin, nout, || c),
+        }
+    }
+
+    fn uniform_with_variance(variance: f64) -> Init {
+        let bound = (3.0 * variance).sqrt();
+        Init::Uniform(-bound, bound)
+    }
+}
+
+fn matrix(nin: usize, nout: usize, mut sample: impl FnMut() -> f64) -> Vec<Vec<f64>> {
+    (0..nout)
+        .map(|_| (0..nin).map(|_| sample()).collect())
+        .collect()
+}
+
+/// Box-Muller transform of two uniform samples.
+fn standard_normal(rng: &mut impl Rng) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos()
+}
+
+/// Orthonormal rows if `nout <= nin`, orthonormal columns otherwise, times `gain`.
+fn orthogonal(nin: usize, nout: usize, gain: f64, rng: &mut impl Rng) -> Vec<Vec<f64>> {
+    let (rows, cols) = (nout.min(nin), nout.max(nin));
+    let mut basis: Vec<Vec<f64>> = Vec::with_capacity(rows);
+    while basis.len() < rows {
+        // modified Gram-Schmidt against the rows accepted so far
+        let mut v: Vec<f64> = (0..cols).map(|_| standard_normal(rng)).collect();
+        for b in &basis {
+            let dot: f64 = v.iter().zip(b).map(|(x, y)| x * y).sum();
+            v.iter_mut().zip(b).for_each(|(x, y)| *x -= dot * y);
+        }
+        let norm = v.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm > 1e-10 {
+            basis.push(v.into_iter().map(|x| x / norm).collect());
+        }
+    }
+    if nout <= nin {
+        basis
+            .into_iter()
+            .map(|row| row.into_iter().map(|x| gain * x).collect())
+            .collect()
+    } else {
+        (0..nout)
+            .map(|i| (0..nin).map(|j| gain * basis[j][i]).collect())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const NIN: usize = 300;
+    const NOUT: usize = 200;
+
+    fn moments(weights: &[Vec<f64>]) -> (f64, f64) {
+        let values: Vec<f64> = weights.iter().flatten().copied().collect();
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n;
+        (mean, variance)
+    }
+
+    fn assert_variance(init: Init, mean: f64, variance: f64) {
+        let weights = init.weights(NIN, NOUT, &mut StdRng::seed_from_u64(7));
+        assert_eq!(weights.len(), NOUT);
+        assert!(weights.iter().all(|row| row.len() == NIN));
+        let (got_mean, got_variance) = moments(&weights);
+        let std = variance.sqrt();
+        assert!(
+            (got_mean - mean).abs() < 0.02 * std.max(1e-3),
+            "{init:?} mean {got_mean}"
+        );
+        assert!(
+            (got_variance - variance).abs() <= 0.05 * variance + 1e-15,
+            "{init:?} variance {got_variance}, expected {variance}"
+        );
+    }
+
+    #[test]
+    fn empirical_variances() {
+        let fan_avg = (NIN + NOUT) as f64 / 2.0;
+        assert_variance(Init::Uniform(-1.0, 1.0), 0.0, 1.0 / 3.0);
+        assert_variance(Init::Uniform(1.0, 3.0), 2.0, 1.0 / 3.0);
+        assert_variance(Init::Normal(0.5, 2.0), 0.5, 4.0);
+        assert_variance(Init::XavierUniform, 0.0, 1.0 / fan_avg);
+        assert_variance(Init::XavierNormal, 0.0, 1.0 / fan_avg);
+        assert_variance(Init::HeUniform, 0.0, 2.0 / NIN as f64);
+        assert_variance(Init::HeNormal, 0.0, 2.0 / NIN as f64);
+        assert_variance(Init::Orthogonal(1.0), 0.0, 1.0 / NIN as f64);
+        assert_variance(Init::Orthogonal(2.0), 0.0, 4.0 / NIN as f64);
+        assert_variance(Init::Constant(0.1), 0.1, 0.0);
+    }
+
+    #[test]
+    fn orthogonal_is_orthonormal() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for (nin, nout) in [(6, 4), (4, 6)] {
+            let w = Init::Orthogonal(1.0).weights(nin, nout, &mut rng);
+            // the shorter side is orthonormal: W W^T = I for wide, W^T W = I for tall
+            let (n, entry): (usize, Box<dyn Fn(usize, usize) -> f64>) = if nout <= nin {
+                (
+                    nout,
+                    Box::new(|i, j| (0..nin).map(|k| w[i][k] * w[j][k]).sum()),
+                )
+            } else {
+                (
+                    nin,
+                    Box::new(|i, j| (0..nout).map(|k| w[k][i] * w[k][j]).sum()),
+                )
+            };
+            for i in 0..n {
+                for j in 0..n {
+                    let expected = if i == j { 1.0 } else { 0.0 };
+                    assert!((entry(i, j) - expected).abs() < 1e-12);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn seeded_weights_repeat() {
+        let draw = || Init::HeNormal.weights(3, 2, &mut StdRng::seed_from_u64(42));
+        assert_eq!(draw(), draw());
+    }
+}
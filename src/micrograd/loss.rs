@@ -0,0 +1,297 @@
+//! Loss functions comparing predictions with `f64` targets.
+//!
+//! Every loss computes one term per sample and combines them as the `Reduction` asks.
+
+use super::{log_softmax, Value};
+
+/// Keeps the logarithms in `bce` finite for probabilities of exactly 0 or 1.
+const EPS: f64 = 1e-12;
+
+/// How per-sample losses are combined into one `Value`.
+///
+/// With no samples every reduction gives 0, and so does `WeightedMean` when the weights add up
+/// to 0, rather than dividing by zero.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Reduction<'a> {
+    #[default]
+    Mean,
+    Sum,
+    /// `sum(w * loss) / sum(w)`.
+    WeightedMean(&'a [f64]),
+    /// `sum(w * loss)`.
+    WeightedSum(&'a [f64]),
+}
+
+/// Mean squared error, `(p - t)^2` per sample.
+pub fn mse(preds: &[Value], targets: &[f64], reduction: Reduction) -> Value {
+    per_sample(preds, targets, reduction, |p, t| {
+        (p - &Value::from(t)).pow(2.0)
+    })
+}
+
+/// Mean absolute error, `|p - t|` per sample.
+pub fn mae(preds: &[Value], targets: &[f64], reduction: Reduction) -> Value {
+    per_sample(preds, targets, reduction, |p, t| {
+        (p - &Value::from(t)).abs()
+    })
+}
+
+/// Quadratic within `delta` of the target and linear beyond it, `0.5 * d^2` or
+/// `delta * (|d| - 0.5 * delta)`.
+pub fn huber(preds: &[Value], targets: &[f64], delta: f64, reduction: Reduction) -> Value {
+    per_sample(preds, targets, reduction, |p, t| {
+        let d = (p - &Value::from(t)).abs();
+        if d.data() <= delta {
+            &d.pow(2.0) * &Value::from(0.5)
+        } else {
+            &(&d - &Value::from(0.5 * delta)) * &Value::from(delta)
+        }
+    })
+}
+
+/// SVM hinge loss, `max(0, 1 - t * p)` per sample, for targets of -1 and 1.
+pub fn hinge(preds: &[Value], targets: &[f64], reduction: Reduction) -> Value {
+    per_sample(preds, targets, reduction, |p, t| {
+        (&Value::from(1.0) - &(&Value::from(t) * p)).relu()
+    })
+}
+
+/// Binary cross-entropy of probabilities in `[0, 1]` against targets in `[0, 1]`.
+pub fn bce(probs: &[Value], targets: &[f64], reduction: Reduction) -> Value {
+    let eps = Value::from(EPS);
+    per_sample(probs, targets, reduction, |p, t| {
+        let log_p = p.max(&eps).ln();
+        let log_not_p = (&Value::from(1.0) - p).max(&eps).ln();
+        -&(&(&log_p * &Value::from(t)) + &(&log_not_p * &Value::from(1.0 - t)))
+    })
+}
+
+/// Binary cross-entropy of raw scores, as `bce` of their sigmoid but without overflowing.
+pub fn bce_with_logits(logits: &[Value], targets: &[f64], reduction: Reduction) -> Value {
+    // max(x, 0) - x * t + ln(1 + exp(-|x|))
+    per_sample(logits, targets, reduction, |x, t| {
+        let softplus = (&Value::from(1.0) + &(-&x.abs()).exp()).ln();
+        &(&x.relu() - &(x * &Value::from(t))) + &softplus
+    })
+}
+
+/// Cross-entropy of one row of unnormalised scores per sample against its class index.
+pub fn cross_entropy(logits: &[Vec<Value>], targets: &[usize], reduction: Reduction) -> Value {
+    assert_eq!(
+        logits.len(),
+        targets.len(),
+        "must have one target per sample"
+    );
+    let losses = logits
+        .iter()
+        .zip(targets)
+        .map(|(row, &class)| -&log_softmax(row)[class])
+        .collect();
+    reduce(losses, reduction)
+}
+
+fn per_sample(
+    preds: &[Value],
+    targets: &[f64],
+    reduction: Reduction,
+    loss: impl Fn(&Value, f64) -> Value,
+) -> Value {
+    assert_eq!(
+        preds.len(),
+        targets.len(),
+        "must have one target per prediction"
+    );
+    let losses = preds
+        .iter()
+        .zip(targets)
+        .map(|(p, &t)| loss(p, t))
+        .collect();
+    reduce(losses, reduction)
+}
+
+fn reduce(losses: Vec<Value>, reduction: Reduction) -> Value {
+    let n = losses.len() as f64;
+    match reduction {
+        Reduction::Sum => sum(losses),
+        Reduction::Mean if losses.is_empty() => Value::from(0.0),
+        Reduction::Mean => &sum(losses) * &Value::from(1.0 / n),
+        Reduction::WeightedSum(weights) => weighted_sum(losses, weights),
+        Reduction::WeightedMean(weights) => {
+            let total: f64 = weights.iter().sum();
+            let weighted = weighted_sum(losses, weights);
+            if total == 0.0 {
+                Value::from(0.0)
+            } else {
+                &weighted * &Value::from(1.0 / total)
+            }
+        }
+    }
+}
+
+/// The sum of `losses`, 0 if there are none.
+fn sum(losses: Vec<Value>) -> Value {
+    if losses.is_empty() {
+        Value::from(0.0)
+    } else {
+        losses.into_iter().sum()
+    }
+}
+
+fn weighted_sum(losses: Vec<Value>, weights: &[f64]) -> Value {
+    assert_eq!(
+        losses.len(),
+        weights.len(),
+        "must have one weight per sample"
+    );
+    sum(losses
+        .iter()
+        .zip(weights)
+        .map(|(l, &w)| l * &Value::from(w))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PREDS: [f64; 4] = [0.3, -1.2, 2.5, 0.9];
+    const TARGETS: [f64; 4] = [0.5, -1.0, 1.0, -1.0];
+    const WEIGHTS: [f64; 4] = [1.0, 0.5, 2.0, 0.25];
+
+    /// Compares backprop gradients of `loss` with central finite differences at `x`.
+    fn gradcheck(loss: impl Fn(&[Value]) -> Value, x: &[f64]) {
+        let inputs: Vec<Value> = x.iter().map(|&v| Value::from(v)).collect();
+        loss(&inputs).backward();
+        let h = 1e-6;
+        for (i, input) in inputs.iter().enumerate() {
+            let at = |offset: f64| {
+                let mut shifted = x.to_vec();
+                shifted[i] += offset;
+                let shifted: Vec<Value> = shifted.into_iter().map(Value::from).collect();
+                loss(&shifted).data()
+            };
+            let numeric = (at(h) - at(-h)) / (2.0 * h);
+            assert!(
+                (input.grad() - numeric).abs() < 1e-5,
+                "input {i}: backprop {} vs numeric {numeric}",
+                input.grad()
+            );
+        }
+    }
+
+    fn values(x: &[f64]) -> Vec<Value> {
+        x.iter().map(|&v| Value::from(v)).collect()
+    }
+
+    fn reductions() -> [Reduction<'static>; 4] {
+        [
+            Reduction::Mean,
+            Reduction::Sum,
+            Reduction::WeightedMean(&WEIGHTS),
+            Reduction::WeightedSum(&WEIGHTS),
+        ]
+    }
+
+    #[test]
+    fn mse_values_and_gradients() {
+        let loss = mse(&values(&PREDS), &TARGETS, Reduction::Mean).data();
+        assert!((loss - (0.04 + 0.04 + 2.25 + 3.61) / 4.0).abs() < 1e-12);
+        for reduction in reductions() {
+            gradcheck(|p| mse(p, &TARGETS, reduction), &PREDS);
+        }
+    }
+
+    #[test]
+    fn weighted_reductions() {
+        let preds = values(&[1.0, 3.0]);
+        let targets = [0.0, 0.0];
+        assert_eq!(mae(&preds, &targets, Reduction::Sum).data(), 4.0);
+        assert_eq!(mae(&preds, &targets, Reduction::Mean).data(), 2.0);
+        let weights = [3.0, 1.0];
+        let weighted = mae(&preds, &targets, Reduction::WeightedSum(&weights)).data();
+        assert_eq!(weighted, 6.0);
+        let weighted = mae(&preds, &targets, Reduction::WeightedMean(&weights)).data();
+        assert_eq!(weighted, 1.5);
+    }
+
+    #[test]
+    fn degenerate_reductions_are_zero() {
+        for reduction in [
+            Reduction::Sum,
+            Reduction::Mean,
+            Reduction::WeightedSum(&[]),
+            Reduction::WeightedMean(&[]),
+        ] {
+            assert_eq!(mse(&[], &[], reduction).data(), 0.0, "{reduction:?}");
+        }
+        let preds = values(&[1.0, 3.0]);
+        let loss = mae(&preds, &[0.0, 0.0], Reduction::WeightedMean(&[0.0, 0.0]));
+        assert_eq!(loss.data(), 0.0);
+        loss.backward();
+        assert!(preds.iter().all(|p| p.grad() == 0.0));
+    }
+
+    #[test]
+    fn mae_and_huber_gradients() {
+        for reduction in reductions() {
+            gradcheck(|p| mae(p, &TARGETS, reduction), &PREDS);
+            gradcheck(|p| huber(p, &TARGETS, 1.0, reduction), &PREDS);
+        }
+        // 0.5 * 0.2^2 inside delta, 1.0 * (3.5 - 0.5) beyond it
+        let loss = huber(&values(&[0.2, 3.5]), &[0.0, 0.0], 1.0, Reduction::Sum);
+        assert!((loss.data() - 3.02).abs() < 1e-12);
+    }
+
+    #[test]
+    fn hinge_gradients() {
+        let targets = [1.0, -1.0, 1.0, -1.0];
+        let loss = hinge(&values(&PREDS), &targets, Reduction::Sum).data();
+        assert!((loss - (0.7 + 0.0 + 0.0 + 1.9)).abs() < 1e-12);
+        for reduction in reductions() {
+            gradcheck(|p| hinge(p, &targets, reduction), &PREDS);
+        }
+    }
+
+    #[test]
+    fn bce_matches_bce_with_logits() {
+        let logits: [f64; 4] = [0.3, -1.2, 2.5, -4.0];
+        let targets = [1.0, 0.0, 0.25, 1.0];
+        let probs: Vec<f64> = logits.iter().map(|x| 1.0 / (1.0 + (-x).exp())).collect();
+        let from_probs = bce(&values(&probs), &targets, Reduction::Mean).data();
+        let from_logits = bce_with_logits(&values(&logits), &targets, Reduction::Mean).data();
+        assert!((from_probs - from_logits).abs() < 1e-9);
+        for reduction in reductions() {
+            gradcheck(|p| bce(p, &targets, reduction), &probs);
+            gradcheck(|x| bce_with_logits(x, &targets, reduction), &logits);
+        }
+        let extreme = bce_with_logits(&values(&[-1000.0]), &[0.0], Reduction::Mean);
+        assert_eq!(extreme.data(), 0.0);
+    }
+
+    #[test]
+    fn cross_entropy_gradients() {
+        let rows = [[2.0, 1.0, 0.1], [0.5, 2.5, -1.0], [1000.0, 0.0, 0.0]];
+        let targets = [0, 2, 0];
+        let logits: Vec<Vec<Value>> = rows.iter().map(|row| values(row)).collect();
+        let loss = cross_entropy(&logits, &targets, Reduction::Sum).data();
+        let expected: f64 = rows
+            .iter()
+            .zip(targets)
+            .take(2)
+            .map(|(row, class)| {
+                let total: f64 = row.iter().map(|x| x.exp()).sum();
+                total.ln() - row[class]
+            })
+            .sum();
+        assert!((loss - expected).abs() < 1e-9);
+        for reduction in reductions().into_iter().take(2) {
+            gradcheck(
+                |x| {
+                    let logits: Vec<Vec<Value>> = x.chunks(3).map(<[Value]>::to_vec).collect();
+                    cross_entropy(&logits, &targets[..2], reduction)
+                },
+                &rows[..2].concat(),
+            );
+        }
+    }
+}
@@ -0,0 +1,351 @@
+//! Classification metrics comparing predictions with integer class labels.
+//!
+//! Everything works on plain numbers, so metrics can be computed from any training loop.
+//! Scores may be `f64`s or `Value`s; [`threshold`] and [`argmax_rows`] turn them into classes.
+
+use std::fmt;
+
+use super::Value;
+
+/// Keeps the logarithms in `log_loss` finite for probabilities of exactly 0 or 1.
+const EPS: f64 = 1e-12;
+
+/// A prediction read as a number.
+pub trait Score {
+    fn score(&self) -> f64;
+}
+
+impl Score for f64 {
+    fn score(&self) -> f64 {
+        *self
+    }
+}
+
+impl Score for Value {
+    fn score(&self) -> f64 {
+        self.data()
+    }
+}
+
+/// How per-class precision, recall and F1 are combined into one number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Average {
+    /// Only the given positive class.
+    Binary(usize),
+    /// The unweighted mean over classes.
+    Macro,
+    /// Computed from the true and false positives of all classes pooled together.
+    Micro,
+}
+
+/// Class 1 where the score exceeds `threshold`, class 0 otherwise.
+pub fn threshold<S: Score>(scores: &[S], threshold: f64) -> Vec<usize> {
+    scores
+        .iter()
+        .map(|s| usize::from(s.score() > threshold))
+        .collect()
+}
+
+/// The class with the highest score in each row.
+pub fn argmax_rows<S: Score>(rows: &[Vec<S>]) -> Vec<usize> {
+    rows.iter()
+        .map(|row| {
+            (0..row.len())
+                .max_by(|&a, &b| row[a].score().total_cmp(&row[b].score()))
+                .expect("row has a score")
+        })
+        .collect()
+}
+
+/// Fraction of predictions equal to their label.
+pub fn accuracy(predicted: &[usize], labels: &[usize]) -> f64 {
+    assert_same_len(predicted.len(), labels.len());
+    let correct = predicted.iter().zip(labels).filter(|(p, l)| p == l).count();
+    correct as f64 / labels.len() as f64
+}
+
+pub fn precision(predicted: &[usize], labels: &[usize], average: Average) -> f64 {
+    ConfusionMatrix::from_predictions(predicted, labels).precision(average)
+}
+
+pub fn recall(predicted: &[usize], labels: &[usize], average: Average) -> f64 {
+    ConfusionMatrix::from_predictions(predicted, labels).recall(average)
+}
+
+pub fn f1(predicted: &[usize], labels: &[usize], average: Average) -> f64 {
+    ConfusionMatrix::from_predictions(predicted, labels).f1(average)
+}
+
+/// Mean binary cross-entropy of probabilities that the label is 1.
+pub fn log_loss<S: Score>(probs: &[S], labels: &[usize]) -> f64 {
+    assert_same_len(probs.len(), labels.len());
+    let total: f64 = probs
+        .iter()
+        .zip(labels)
+        .map(|(p, &label)| {
+            let p = p.score().clamp(EPS, 1.0 - EPS);
+            if label == 1 {
+                -p.ln()
+            } else {
+                -(1.0 - p).ln()
+            }
+        })
+        .sum();
+    total / labels.len() as f64
+}
+
+/// Area under the ROC curve of scores for class 1: the chance that a random positive scores
+/// higher than a random negative, counting ties as half. `NaN` unless both classes occur.
+pub fn roc_auc<S: Score>(scores: &[S], labels: &[usize]) -> f64 {
+    assert_same_len(scores.len(), labels.len());
+    let mut order: Vec<usize> = (0..scores.len()).collect();
+    order.sort_by(|&a, &b| scores[a].score().total_cmp(&scores[b].score()));
+
+    // sum of the (average, for ties) ranks of the positives
+    let mut positive_ranks = 0.0;
+    let mut start = 0;
+    while start < order.len() {
+        let score = scores[order[start]].score();
+        let end = start
+            + order[start..]
+                .iter()
+                .take_while(|&&i| scores[i].score() == score)
+                .count();
+        let rank = (start + end + 1) as f64 / 2.0;
+        let positives = order[start..end]
+            .iter()
+            .filter(|&&i| labels[i] == 1)
+            .count();
+        positive_ranks += rank * positives as f64;
+        start = end;
+    }
+    let positives = labels.iter().filter(|&&l| l == 1).count() as f64;
+    let negatives = labels.len() as f64 - positives;
+    (positive_ranks - positives * (positives + 1.0) / 2.0) / (positives * negatives)
+}
+
+/// Counts of each label, by row, against each prediction, by column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfusionMatrix {
+    counts: Vec<Vec<usize>>,
+}
+
+impl ConfusionMatrix {
+    pub fn new(n_classes: usize) -> ConfusionMatrix {
+        ConfusionMatrix {
+            counts: vec![vec![0; n_classes]; n_classes],
+        }
+    }
+
+    /// A matrix with as many classes as the largest prediction or label needs.
+    pub fn from_predictions(predicted: &[usize], labels: &[usize]) -> ConfusionMatrix {
+        let n_classes = predicted.iter().chain(labels).max().map_or(0, |&c| c + 1);
+        let mut matrix = ConfusionMatrix::new(n_classes.max(2));
+        matrix.update(predicted, labels);
+        matrix
+    }
+
+    /// Adds more predictions, e.g. one batch at a time.
+    pub fn update(&mut self, predicted: &[usize], labels: &[usize]) {
+        assert_same_len(predicted.len(), labels.len());
+        for (&p, &l) in predicted.iter().zip(labels) {
+            assert!(
+                p < self.n_classes() && l < self.n_classes(),
+                "class out of range"
+            );
+            self.counts[l][p] += 1;
+        }
+    }
+
+    pub fn n_classes(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Samples of class `label` predicted as `predicted`.
+    pub fn get(&self, label: usize, predicted: usize) -> usize {
+        self.counts[label][predicted]
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().flatten().sum()
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        let correct: usize = (0..self.n_classes()).map(|c| self.counts[c][c]).sum();
+        correct as f64 / self.total() as f64
+    }
+
+    pub fn precision(&self, average: Average) -> f64 {
+        self.average(average, |tp, fp, _| ratio(tp, tp + fp))
+    }
+
+    pub fn recall(&self, average: Average) -> f64 {
+        self.average(average, |tp, _, fn_| ratio(tp, tp + fn_))
+    }
+
+    pub fn f1(&self, average: Average) -> f64 {
+        self.average(average, |tp, fp, fn_| ratio(2 * tp, 2 * tp + fp + fn_))
+    }
+
+    /// True positives, false positives and false negatives of `class`.
+    fn outcomes(&self, class: usize) -> (usize, usize, usize) {
+        let tp = self.counts[class][class];
+        let predicted: usize = self.counts.iter().map(|row| row[class]).sum();
+        let actual: usize = self.counts[class].iter().sum();
+        (tp, predicted - tp, actual - tp)
+    }
+
+    fn average(&self, average: Average, metric: impl Fn(usize, usize, usize) -> f64) -> f64 {
+        let per_class = |class| {
+            let (tp, fp, fn_) = self.outcomes(class);
+            metric(tp, fp, fn_)
+        };
+        match average {
+            Average::Binary(positive) => per_class(positive),
+            Average::Macro => {
+                (0..self.n_classes()).map(per_class).sum::<f64>() / self.n_classes() as f64
+            }
+            Average::Micro => {
+                let (tp, fp, fn_) = (0..self.n_classes())
+                    .map(|class| self.outcomes(class))
+                    .fold((0, 0, 0), |acc, o| (acc.0 + o.0, acc.1 + o.1, acc.2 + o.2));
+                metric(tp, fp, fn_)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ConfusionMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .counts
+            .iter()
+            .flatten()
+            .map(|c| c.to_string().len())
+            .chain([self.n_classes().to_string().len(), "label".len()])
+            .max()
+            .unwrap_or(1);
+        write!(f, "{:>width$} |", "label")?;
+        for c in 0..self.n_classes() {
+            write!(f, " {c:>width$}")?;
+        }
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{}-+{}",
+            "-".repeat(width),
+            "-".repeat((width + 1) * self.n_classes())
+        )?;
+        for (label, row) in self.counts.iter().enumerate() {
+            write!(f, "{label:>width$} |")?;
+            for count in row {
+                write!(f, " {count:>width$}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// 0 when nothing was counted, as no predictions or no samples of a class make the metric
+/// undefined.
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+fn assert_same_len(predictions: usize, labels: usize) {
+    assert_eq!(predictions, labels, "must have one label per prediction");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // three classes, labels by row: 0 0 0 1 1 2 2 2
+    const LABELS: [usize; 8] = [0, 0, 0, 1, 1, 2, 2, 2];
+    const PREDICTED: [usize; 8] = [0, 0, 1, 1, 2, 2, 2, 0];
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-12
+    }
+
+    #[test]
+    fn confusion_matrix_metrics() {
+        let matrix = ConfusionMatrix::from_predictions(&PREDICTED, &LABELS);
+        assert_eq!(matrix.get(0, 1), 1);
+        assert_eq!(matrix.get(2, 0), 1);
+        assert!(close(matrix.accuracy(), 5.0 / 8.0));
+        assert!(close(accuracy(&PREDICTED, &LABELS), 5.0 / 8.0));
+
+        // per class precision 2/3, 1/2, 2/3 and recall 2/3, 1/2, 2/3
+        assert!(close(
+            precision(&PREDICTED, &LABELS, Average::Binary(1)),
+            0.5
+        ));
+        assert!(close(
+            recall(&PREDICTED, &LABELS, Average::Binary(0)),
+            2.0 / 3.0
+        ));
+        let macro_precision = (2.0 / 3.0 + 0.5 + 2.0 / 3.0) / 3.0;
+        assert!(close(matrix.precision(Average::Macro), macro_precision));
+        assert!(close(matrix.f1(Average::Macro), macro_precision));
+        // pooled over classes micro averages all equal accuracy
+        assert!(close(matrix.precision(Average::Micro), 5.0 / 8.0));
+        assert!(close(f1(&PREDICTED, &LABELS, Average::Micro), 5.0 / 8.0));
+
+        let binary = ConfusionMatrix::from_predictions(&[1, 1, 0, 0], &[1, 0, 0, 0]);
+        assert!(close(binary.precision(Average::Binary(1)), 0.5));
+        assert!(close(binary.recall(Average::Binary(1)), 1.0));
+        assert!(close(binary.f1(Average::Binary(1)), 2.0 / 3.0));
+        assert_eq!(
+            ConfusionMatrix::from_predictions(&[0], &[0]).f1(Average::Binary(1)),
+            0.0
+        );
+    }
+
+    #[test]
+    fn confusion_matrix_prints_as_table() {
+        let mut matrix = ConfusionMatrix::new(2);
+        matrix.update(&[0, 1, 1], &[0, 1, 0]);
+        matrix.update(&[1; 10], &[1; 10]);
+        assert_eq!(
+            matrix.to_string(),
+            "label |     0     1\n\
+             ------+------------\n    \
+                 0 |     1     1\n    \
+                 1 |     0    11\n"
+        );
+    }
+
+    #[test]
+    fn scores_become_classes() {
+        let scores = [Value::from(-0.5), Value::from(0.2), Value::from(0.0)];
+        assert_eq!(threshold(&scores, 0.0), [0, 1, 0]);
+        assert_eq!(
+            argmax_rows(&[vec![0.1, 0.7, 0.2], vec![3.0, -1.0, 0.0]]),
+            [1, 0]
+        );
+    }
+
+    #[test]
+    fn log_loss_and_roc_auc() {
+        let probs = [0.9, 0.2, 0.6, 0.4];
+        let labels = [1, 0, 1, 0];
+        let expected = -(0.9f64.ln() + 0.8f64.ln() + 0.6f64.ln() + 0.6f64.ln()) / 4.0;
+        assert!(close(log_loss(&probs, &labels), expected));
+        assert!(log_loss(&[0.0], &[1]).is_finite());
+
+        assert!(close(roc_auc(&probs, &labels), 1.0));
+        assert!(close(roc_auc(&probs, &[0, 1, 0, 1]), 0.0));
+        // each positive counts the negatives scored below it, and the tie at 0.5 as half
+        let scores = [0.1, 0.35, 0.4, 0.5, 0.5, 0.8];
+        let labels = [0, 1, 0, 1, 0, 1];
+        let expected = (1.0 + 2.5 + 3.0) / 9.0;
+        assert!(close(roc_auc(&scores, &labels), expected));
+        assert!(roc_auc(&[0.3, 0.6], &[1, 1]).is_nan());
+    }
+}
@@ -1,10 +1,42 @@
 // #[macro_use] # double check if needed
 extern crate impl_ops;
 
+pub mod activation;
+pub mod checkpoint;
+pub mod codegen;
+pub mod data;
+pub mod dropout;
 pub mod engine;
+mod expression;
+pub mod init;
+pub mod loss;
+pub mod metrics;
+pub mod module;
 pub mod neural_net;
+pub mod norm;
+pub mod optim;
+pub mod regularize;
+pub mod resume;
+pub mod scheduler;
+pub mod serialize;
+pub mod simplify;
+pub mod train;
 pub mod visualize;
 
+pub use activation::*;
+pub use checkpoint::*;
+pub use data::*;
+pub use dropout::*;
 pub use engine::*;
+pub use init::*;
+pub use module::*;
 pub use neural_net::*;
+pub use norm::*;
+pub use optim::*;
+pub use regularize::*;
+pub use resume::*;
+pub use scheduler::*;
+pub use serialize::*;
+pub use simplify::*;
+pub use train::*;
 pub use visualize::*;
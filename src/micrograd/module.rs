@@ -0,0 +1,368 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashSet},
+    fmt,
+    rc::Rc,
+};
+
+use rand_chacha::ChaCha12Rng;
+
+use super::{Activation, Value};
+
+/// Parameter values by their `named_parameters` names.
+pub type StateDict = BTreeMap<String, f64>;
+
+/// State a module keeps besides its parameters, shared with the module like a parameter
+/// `Value` is, so it can be saved and restored to resume training exactly.
+#[derive(Debug, Clone)]
+pub enum Buffer {
+    /// Statistics updated while training, e.g. batch normalization's running mean.
+    Values(Rc<RefCell<Vec<f64>>>),
+    /// A random generator, e.g. the one dropout draws its masks from.
+    Rng(Rc<RefCell<ChaCha12Rng>>),
+}
+
+/// A differentiable function of a row of values with trainable parameters.
+///
+/// Containers pass `set_training` on to their children, so layers that behave differently
+/// during training, such as dropout, can be switched with one call on the whole model.
+pub trait Module {
+    fn forward(&self, input: &[Value]) -> Vec<Value>;
+
+    fn parameters(&self) -> Vec<Value> {
+        self.named_parameters()
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect()
+    }
+
+    /// Every parameter with a dotted path naming it, e.g. `layers.0.neurons.2.weight.1`.
+    fn named_parameters(&self) -> Vec<(String, Value)>;
+
+    /// A copy of every parameter's value, by name.
+    fn state_dict(&self) -> StateDict {
+        self.named_parameters()
+            .into_iter()
+            .map(|(name, value)| (name, value.data()))
+            .collect()
+    }
+
+    /// Copies the values of `state` into the parameters of the same name. Parameters missing
+    /// from `state` keep their values, and entries naming no parameter are ignored; both are
+    /// listed in the returned report.
+    fn load_state_dict(&self, state: &StateDict) -> LoadReport {
+        let named = self.named_parameters();
+        let mut report = LoadReport::default();
+        for (name, value) in &named {
+            match state.get(name) {
+                Some(&data) => value.borrow_mut().data = data,
+                None => report.missing_keys.push(name.clone()),
+            }
+        }
+        let names: HashSet<&str> = named.iter().map(|(name, _)| name.as_str()).collect();
+        report.unexpected_keys = state
+            .keys()
+            .filter(|key| !names.contains(key.as_str()))
+            .cloned()
+            .collect();
+        report
+    }
+
+    /// Every buffer with a dotted path naming it, e.g. `layers.0.dropout.rng`. Modules without
+    /// state besides their parameters have none.
+    fn named_buffers(&self) -> Vec<(String, Buffer)> {
+        Vec::new()
+    }
+
+    fn set_training(&mut self, _training: bool) {}
+
+    fn is_training(&self) -> bool {
+        false
+    }
+
+    fn train(&mut self) {
+        self.set_training(true);
+    }
+
+    fn eval(&mut self) {
+        self.set_training(false);
+    }
+
+    fn zero_grad(&self) {
+        for p in self.parameters() {
+            p.borrow_mut().grad = 0.0;
+        }
+    }
+
+    /// Runs `forward` on every row of a batch.
+    fn forward_batch(&self, inputs: &[Vec<Value>]) -> Vec<Vec<Value>> {
+        inputs.iter().map(|input| self.forward(input)).collect()
+    }
+}
+
+/// The keys that did not line up when loading a [`StateDict`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadReport {
+    /// Parameters of the module that the state did not have, in parameter order.
+    pub missing_keys: Vec<String>,
+    /// Entries of the state that name no parameter of the module, in key order.
+    pub unexpected_keys: Vec<String>,
+}
+
+impl LoadReport {
+    /// Whether every parameter was loaded and every entry used.
+    pub fn is_exact(&self) -> bool {
+        self.missing_keys.is_empty() && self.unexpected_keys.is_empty()
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "missing keys: [{}], unexpected keys: [{}]",
+            self.missing_keys.join(", "),
+            self.unexpected_keys.join(", ")
+        )
+    }
+}
+
+/// Prepends `prefix.` to the names of a child's parameters or buffers.
+pub(crate) fn prefixed<T>(prefix: &str, named: Vec<(String, T)>) -> Vec<(String, T)> {
+    named
+        .into_iter()
+        .map(|(name, value)| (format!("{prefix}.{name}"), value))
+        .collect()
+}
+
+/// Applies the activation to every value; it has no parameters.
+impl Module for Activation {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        input.iter().map(|x| self.apply(x)).collect()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        Vec::new()
+    }
+}
+
+/// Runs modules one after the other, feeding each one's output into the next.
+///
+/// Parameters are named by the position of their module, e.g. `1.neurons.0.bias`.
+pub struct Sequential {
+    modules: Vec<Box<dyn Module>>,
+    training: bool,
+}
+
+impl Sequential {
+    pub fn new() -> Sequential {
+        Sequential {
+            modules: Vec::new(),
+            training: false,
+        }
+    }
+
+    pub fn with_module(mut self, mut module: impl Module + 'static) -> Sequential {
+        module.set_training(self.training);
+        self.modules.push(Box::new(module));
+        self
+    }
+
+    pub fn modules(&self) -> &[Box<dyn Module>] {
+        &self.modules
+    }
+}
+
+impl Default for Sequential {
+    fn default() -> Sequential {
+        Sequential::new()
+    }
+}
+
+impl Module for Sequential {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        self.modules
+            .iter()
+            .fold(input.to_vec(), |acc, module| module.forward(&acc))
+    }
+
+    /// Passes the whole batch through each module in turn, so modules such as batch
+    /// normalization see every row at once.
+    fn forward_batch(&self, inputs: &[Vec<Value>]) -> Vec<Vec<Value>> {
+        self.modules
+            .iter()
+            .fold(inputs.to_vec(), |acc, module| module.forward_batch(&acc))
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        self.modules
+            .iter()
+            .enumerate()
+            .flat_map(|(i, module)| prefixed(&i.to_string(), module.named_parameters()))
+            .collect()
+    }
+
+    fn named_buffers(&self) -> Vec<(String, Buffer)> {
+        self.modules
+            .iter()
+            .enumerate()
+            .flat_map(|(i, module)| prefixed(&i.to_string(), module.named_buffers()))
+            .collect()
+    }
+
+    fn set_training(&mut self, training: bool) {
+        self.training = training;
+        self.modules
+            .iter_mut()
+            .for_each(|module| module.set_training(training));
+    }
+
+    fn is_training(&self) -> bool {
+        self.training
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micrograd::{Layer, Neuron, MLP};
+
+    /// Scales its input by a single trainable factor.
+    struct Scale {
+        factor: Value,
+        training: bool,
+    }
+
+    impl Module for Scale {
+        fn forward(&self, input: &[Value]) -> Vec<Value> {
+            input.iter().map(|x| x * &self.factor).collect()
+        }
+
+        fn named_parameters(&self) -> Vec<(String, Value)> {
+            vec![("factor".to_string(), self.factor.clone())]
+        }
+
+        fn set_training(&mut self, training: bool) {
+            self.training = training;
+        }
+
+        fn is_training(&self) -> bool {
+            self.training
+        }
+    }
+
+    #[test]
+    fn named_parameters_follow_structure() {
+        let neuron = Neuron::from((vec![1.0, 2.0], 0.5, false));
+        let names: Vec<String> = neuron
+            .named_parameters()
+            .into_iter()
+            .map(|(n, _)| n)
+            .collect();
+        assert_eq!(names, ["bias", "weight.0", "weight.1"]);
+
+        let mlp = MLP::new(vec![2, 3, 1]);
+        let named = mlp.named_parameters();
+        assert_eq!(named.len(), mlp.parameters().len());
+        assert_eq!(named[0].0, "layers.0.neurons.0.bias");
+        assert_eq!(named[12].0, "layers.1.neurons.0.weight.2");
+        assert!(named
+            .iter()
+            .zip(mlp.parameters())
+            .all(|((_, a), b)| *a == b));
+    }
+
+    #[test]
+    fn sequential_composes_modules() {
+        let factor = Value::from(3.0);
+        let mut model = Sequential::new()
+            .with_module(Layer::from(vec![Neuron::from((
+                vec![1.0, -1.0],
+                0.0,
+                false,
+            ))]))
+            .with_module(Scale {
+                factor: factor.clone(),
+                training: false,
+            })
+            .with_module(Activation::Relu);
+
+        let out = model.forward(&[Value::from(5.0), Value::from(2.0)]);
+        assert_eq!(out[0].data(), 9.0);
+        let names: Vec<String> = model
+            .named_parameters()
+            .into_iter()
+            .map(|(n, _)| n)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "0.neurons.0.bias",
+                "0.neurons.0.weight.0",
+                "0.neurons.0.weight.1",
+                "1.factor"
+            ]
+        );
+
+        out[0].backward();
+        assert_eq!(factor.grad(), 3.0);
+        model.zero_grad();
+        assert_eq!(factor.grad(), 0.0);
+
+        model.train();
+        assert!(model.is_training());
+        let batch = model.forward_batch(&[vec![Value::from(1.0), Value::from(2.0)]]);
+        assert_eq!(batch[0][0].data(), 0.0);
+    }
+
+    #[test]
+    fn state_dict_round_trips_and_reports_mismatches() {
+        let source = MLP::builder(2)
+            .layer(3, true)
+            .layer(1, false)
+            .with_seed(1)
+            .build();
+        let state = source.state_dict();
+        assert_eq!(state.len(), 13);
+        assert_eq!(
+            state["layers.1.neurons.0.weight.2"],
+            source.layers()[1].parameters()[3].data()
+        );
+
+        let target = MLP::builder(2)
+            .layer(3, true)
+            .layer(1, false)
+            .with_seed(2)
+            .build();
+        let report = target.load_state_dict(&state);
+        assert!(report.is_exact(), "{report}");
+        assert_eq!(target.state_dict(), state);
+
+        let mut partial = state.clone();
+        partial.remove("layers.0.neurons.2.bias");
+        partial.insert("layers.2.neurons.0.bias".to_string(), 1.0);
+        let wider = MLP::builder(2).layer(4, true).layer(1, false).build();
+        let before = wider.state_dict();
+        let report = wider.load_state_dict(&partial);
+        assert_eq!(
+            report.missing_keys,
+            [
+                "layers.0.neurons.2.bias",
+                "layers.0.neurons.3.bias",
+                "layers.0.neurons.3.weight.0",
+                "layers.0.neurons.3.weight.1",
+                "layers.1.neurons.0.weight.3"
+            ]
+        );
+        assert_eq!(report.unexpected_keys, ["layers.2.neurons.0.bias"]);
+        let loaded = wider.state_dict();
+        assert_eq!(
+            loaded["layers.0.neurons.3.bias"],
+            before["layers.0.neurons.3.bias"]
+        );
+        assert_eq!(
+            loaded["layers.1.neurons.0.weight.0"],
+            state["layers.1.neurons.0.weight.0"]
+        );
+    }
+}
@@ -1,16 +1,22 @@
-use rand::distributions::{Distribution, Uniform};
+use std::fmt;
 
-use super::Value;
+use rand::{
+    distributions::{Distribution, Uniform},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
+
+use super::{module::prefixed, Activation, Buffer, Dropout, Init, Module, Value};
 
 #[derive(Debug)]
-struct Neuron {
-    weights: Vec<Value>,
-    bias: Value,
-    nonlin: bool,
+pub struct Neuron {
+    pub(crate) weights: Vec<Value>,
+    pub(crate) bias: Value,
+    pub(crate) activation: Activation,
 }
 
 impl Neuron {
-    pub fn new(nin: usize, nonlin: bool) -> Neuron {
+    pub fn new(nin: usize, activation: impl Into<Activation>) -> Neuron {
         let mut rng = rand::thread_rng();
         let uniform = Uniform::new_inclusive(-1.0, 1.0);
         //  TODO: make into 1 line loop
@@ -20,13 +26,13 @@ impl Neuron {
         }
         // println!("{:?}", weights);
         Neuron {
-            weights: weights,
+            weights,
             bias: Value::from(0.0),
-            nonlin,
+            activation: activation.into(),
         }
     }
 
-    pub fn forward(&self, activations: &Vec<Value>) -> Value {
+    pub fn forward(&self, activations: &[Value]) -> Value {
         let mut result: Value = self
             .weights
             .iter()
@@ -35,18 +41,29 @@ impl Neuron {
             // .collect()
             .sum();
         result += &self.bias;
-        let output = if self.nonlin { result.relu() } else { result };
+        self.activation.apply(&result)
+    }
+}
 
-        output
+impl Module for Neuron {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        vec![Neuron::forward(self, input)]
     }
-    pub fn parameters(&self) -> Vec<Value> {
-        let mut output = self.weights.clone();
-        output.insert(0, self.bias.clone());
-        output
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        let weights = self
+            .weights
+            .iter()
+            .enumerate()
+            .map(|(k, w)| (format!("weight.{k}"), w.clone()));
+        std::iter::once(("bias".to_string(), self.bias.clone()))
+            .chain(weights)
+            .collect()
     }
 }
-impl From<(Vec<f64>, f64, bool)> for Neuron {
-    fn from(vals: (Vec<f64>, f64, bool)) -> Neuron {
+
+impl<A: Into<Activation>> From<(Vec<f64>, f64, A)> for Neuron {
+    fn from(vals: (Vec<f64>, f64, A)) -> Neuron {
         Neuron {
             weights: vals
                 .0
@@ -54,99 +71,400 @@ impl From<(Vec<f64>, f64, bool)> for Neuron {
                 .map(|val| Value::from(val.to_owned()))
                 .collect(),
             bias: Value::from(vals.1),
-            nonlin: vals.2,
+            activation: vals.2.into(),
         }
     }
 }
 
 #[derive(Debug)]
-struct Layer {
-    neurons: Vec<Neuron>,
-    nin: usize,
+pub struct Layer {
+    pub(crate) neurons: Vec<Neuron>,
+    pub(crate) nin: usize,
+    /// Kept on the layer too, so a layer without neurons still knows it.
+    pub(crate) activation: Activation,
+    pub(crate) dropout: Option<Dropout>,
+    pub(crate) training: bool,
 }
 
 impl Layer {
-    pub fn new(nin: usize, nout: usize, nonlin: bool) -> Layer {
+    pub fn new(nin: usize, nout: usize, activation: impl Into<Activation>) -> Layer {
+        let activation = activation.into();
         Layer {
-            neurons: (0..nout).map(|_| Neuron::new(nin, nonlin)).collect(),
+            neurons: (0..nout).map(|_| Neuron::new(nin, activation)).collect(),
             nin,
+            activation,
+            dropout: None,
+            training: false,
         }
     }
 
-    pub fn parameters(&self) -> Vec<Value> {
-        self.neurons
-            .iter()
-            .fold(Vec::new(), |mut acc: Vec<Value>, neuron: &Neuron| {
-                acc.extend(neuron.parameters());
-                acc
-            })
+    /// A layer whose weights are drawn from `init` using `rng`, with zero biases.
+    pub fn with_init(
+        nin: usize,
+        nout: usize,
+        activation: impl Into<Activation>,
+        init: Init,
+        rng: &mut impl Rng,
+    ) -> Layer {
+        let activation = activation.into();
+        Layer {
+            neurons: init
+                .weights(nin, nout, rng)
+                .into_iter()
+                .map(|weights| Neuron::from((weights, 0.0, activation)))
+                .collect(),
+            nin,
+            activation,
+            dropout: None,
+            training: false,
+        }
+    }
+
+    pub fn nin(&self) -> usize {
+        self.nin
     }
 
-    pub fn forward(&self, activations: &Vec<Value>) -> Vec<Value> {
+    pub fn nout(&self) -> usize {
+        self.neurons.len()
+    }
+
+    pub fn activation(&self) -> Activation {
+        self.activation
+    }
+
+    /// Applies `dropout` to the layer's outputs while training.
+    pub fn with_dropout(mut self, mut dropout: Dropout) -> Layer {
+        dropout.set_training(self.training);
+        self.dropout = Some(dropout);
+        self
+    }
+
+    pub fn dropout(&self) -> Option<&Dropout> {
+        self.dropout.as_ref()
+    }
+}
+
+impl Module for Layer {
+    fn forward(&self, activations: &[Value]) -> Vec<Value> {
         assert_eq!(
             activations.len(),
             self.nin,
             "activations must be same length as inputs to neurons"
         );
-        let out = self
+        let outputs: Vec<Value> = self
             .neurons
             .iter()
             .map(|neuron| neuron.forward(activations))
             .collect();
-        // .fold(Vec::new(), |mut acc: Vec<Value>, neuron: &Neuron| {
-        //     acc.extend(neuron.forward());
-        //     acc
-        // });
-        out
+        match &self.dropout {
+            Some(dropout) => dropout.forward(&outputs),
+            None => outputs,
+        }
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        self.neurons
+            .iter()
+            .enumerate()
+            .flat_map(|(j, neuron)| prefixed(&format!("neurons.{j}"), neuron.named_parameters()))
+            .collect()
+    }
+
+    fn named_buffers(&self) -> Vec<(String, Buffer)> {
+        self.dropout.as_ref().map_or_else(Vec::new, |dropout| {
+            prefixed("dropout", dropout.named_buffers())
+        })
+    }
+
+    fn set_training(&mut self, training: bool) {
+        self.training = training;
+        if let Some(dropout) = &mut self.dropout {
+            dropout.set_training(training);
+        }
+    }
+
+    fn is_training(&self) -> bool {
+        self.training
+    }
+}
+
+/// One line, e.g. `Layer(2 -> 16, relu, dropout 0.5)`.
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Layer({} -> {}, {}",
+            self.nin,
+            self.nout(),
+            self.activation()
+        )?;
+        if let Some(dropout) = &self.dropout {
+            write!(f, ", dropout {}", dropout.p())?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// A layer of the given neurons, which must all take the same number of inputs, with the
+/// activation of the first one.
+impl From<Vec<Neuron>> for Layer {
+    fn from(neurons: Vec<Neuron>) -> Layer {
+        let nin = neurons.first().map_or(0, |neuron| neuron.weights.len());
+        let activation = neurons
+            .first()
+            .map_or(Activation::Identity, |neuron| neuron.activation);
+        assert!(
+            neurons.iter().all(|neuron| neuron.weights.len() == nin),
+            "neurons must take the same number of inputs"
+        );
+        Layer {
+            neurons,
+            nin,
+            activation,
+            dropout: None,
+            training: false,
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct MLP {
-    layers: Vec<Layer>,
+    pub(crate) layers: Vec<Layer>,
+    pub(crate) training: bool,
 }
 
 impl MLP {
+    /// ReLU on every layer but the last, which is left linear.
     pub fn new(n_layer_size: Vec<usize>) -> MLP {
-        let layers = (0..n_layer_size.len() - 1)
-            .map(|i| {
-                Layer::new(
-                    n_layer_size[i].clone(),
-                    n_layer_size[i + 1].clone(),
-                    i != n_layer_size.len() - 2,
-                )
+        let last = n_layer_size.len().saturating_sub(2);
+        n_layer_size[1..]
+            .iter()
+            .enumerate()
+            .fold(MLP::builder(n_layer_size[0]), |builder, (i, &size)| {
+                builder.layer(size, i != last)
+            })
+            .build()
+    }
+
+    /// Starts an `MLP` taking `nin` inputs, with layers and their activations added one by one.
+    pub fn builder(nin: usize) -> MLPBuilder {
+        MLPBuilder {
+            nin,
+            layers: Vec::new(),
+            init: Init::default(),
+            seed: None,
+        }
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn last_layer(&self) -> Vec<Value> {
+        self.layers.last().expect("layer exists").parameters()
+    }
+
+    /// A table of each layer's sizes, activation, dropout and parameter count, with the
+    /// total at the bottom.
+    pub fn summary(&self) -> String {
+        let header = [
+            "layer",
+            "input",
+            "output",
+            "activation",
+            "dropout",
+            "parameters",
+        ];
+        let mut rows: Vec<[String; 6]> = self
+            .layers
+            .iter()
+            .enumerate()
+            .map(|(i, layer)| {
+                [
+                    i.to_string(),
+                    layer.nin().to_string(),
+                    layer.nout().to_string(),
+                    layer.activation().to_string(),
+                    layer
+                        .dropout()
+                        .map_or("-".to_string(), |dropout| dropout.p().to_string()),
+                    layer.parameters().len().to_string(),
+                ]
             })
             .collect();
-        MLP { layers }
+        let total = self.parameters().len().to_string();
+        rows.push([
+            "total".to_string(),
+            self.nin().to_string(),
+            self.nout().to_string(),
+            String::new(),
+            String::new(),
+            total,
+        ]);
+
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+        let line = |cells: &[String]| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(widths)
+                .enumerate()
+                .map(|(c, (cell, width))| match c {
+                    // names are left-aligned, numbers right-aligned
+                    0 | 3 => format!("{cell:<width$}"),
+                    _ => format!("{cell:>width$}"),
+                })
+                .collect();
+            cells.join("  ").trim_end().to_string() + "\n"
+        };
+        let rule = "-".repeat(widths.iter().sum::<usize>() + 2 * (widths.len() - 1)) + "\n";
+        let (total, layers) = rows.split_last().expect("total row exists");
+        let mut table = line(&header.map(String::from)) + &rule;
+        for row in layers {
+            table += &line(row);
+        }
+        table + &rule + &line(total)
     }
 
-    pub fn parameters(&self) -> Vec<Value> {
+    /// Inputs of the first layer, 0 without layers.
+    pub fn nin(&self) -> usize {
+        self.layers.first().map_or(0, Layer::nin)
+    }
+
+    /// Outputs of the last layer, 0 without layers.
+    pub fn nout(&self) -> usize {
+        self.layers.last().map_or(0, Layer::nout)
+    }
+}
+
+/// One line of sizes and the parameter count, e.g. `MLP(2 -> 16 -> 1, 65 parameters)`; use
+/// [`MLP::summary`] for the layers in detail.
+impl fmt::Display for MLP {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MLP({}", self.nin())?;
+        for layer in &self.layers {
+            write!(f, " -> {}", layer.nout())?;
+        }
+        write!(f, ", {} parameters)", self.parameters().len())
+    }
+}
+
+impl Module for MLP {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
         self.layers
             .iter()
-            .fold(Vec::new(), |mut acc: Vec<Value>, layer: &Layer| {
-                acc.extend(layer.parameters());
-                acc
-            })
+            .fold(input.to_vec(), |acc, layer| layer.forward(&acc))
     }
 
-    pub fn last_layer(&self) -> Vec<Value> {
-        self.layers.last().expect("layer exists").parameters()
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        self.layers
+            .iter()
+            .enumerate()
+            .flat_map(|(i, layer)| prefixed(&format!("layers.{i}"), layer.named_parameters()))
+            .collect()
     }
 
-    pub fn forward(&self, input: Vec<Value>) -> Vec<Value> {
+    fn named_buffers(&self) -> Vec<(String, Buffer)> {
         self.layers
             .iter()
-            .fold(input, |acc, layer| layer.forward(&acc))
+            .enumerate()
+            .flat_map(|(i, layer)| prefixed(&format!("layers.{i}"), layer.named_buffers()))
+            .collect()
+    }
+
+    fn set_training(&mut self, training: bool) {
+        self.training = training;
+        self.layers
+            .iter_mut()
+            .for_each(|layer| layer.set_training(training));
+    }
+
+    fn is_training(&self) -> bool {
+        self.training
+    }
+}
+
+/// Collects the size, activation and initialization of each layer of an `MLP`.
+#[derive(Debug, Clone)]
+pub struct MLPBuilder {
+    nin: usize,
+    layers: Vec<(usize, Activation, Option<Init>, Option<f64>)>,
+    init: Init,
+    seed: Option<u64>,
+}
+
+impl MLPBuilder {
+    pub fn layer(mut self, nout: usize, activation: impl Into<Activation>) -> MLPBuilder {
+        self.layers.push((nout, activation.into(), None, None));
+        self
+    }
+
+    /// Adds a layer initialized with `init` instead of the builder's default.
+    pub fn layer_with_init(
+        mut self,
+        nout: usize,
+        activation: impl Into<Activation>,
+        init: Init,
+    ) -> MLPBuilder {
+        self.layers
+            .push((nout, activation.into(), Some(init), None));
+        self
+    }
+
+    /// Drops the outputs of the most recently added layer with probability `p` while
+    /// training.
+    pub fn dropout(mut self, p: f64) -> MLPBuilder {
+        let last = self.layers.last_mut().expect("dropout follows a layer");
+        last.3 = Some(p);
+        self
+    }
+
+    /// Initialization for layers added without their own, `Uniform(-1, 1)` unless set.
+    pub fn with_init(mut self, init: Init) -> MLPBuilder {
+        self.init = init;
+        self
+    }
+
+    /// Draws all weights from a generator seeded with `seed`, so builds repeat exactly.
+    pub fn with_seed(mut self, seed: u64) -> MLPBuilder {
+        self.seed = Some(seed);
+        self
     }
-    pub fn zero_grad(&self) {
-        for p in self.parameters() {
-            p.borrow_mut().grad = 0.0;
+
+    pub fn build(self) -> MLP {
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let mut nin = self.nin;
+        let mut layers: Vec<Layer> = self
+            .layers
+            .iter()
+            .map(|&(nout, activation, init, _)| {
+                let init = init.unwrap_or(self.init);
+                let layer = Layer::with_init(nin, nout, activation, init, &mut rng);
+                nin = nout;
+                layer
+            })
+            .collect();
+        // seeded after all weights are drawn, so adding dropout leaves the weights unchanged
+        for (layer, &(.., p)) in layers.iter_mut().zip(&self.layers) {
+            if let Some(p) = p {
+                layer.dropout = Some(Dropout::new(p).with_seed(rng.gen()));
+            }
+        }
+        MLP {
+            layers,
+            training: false,
         }
     }
 }
 
 #[cfg(test)]
-
 mod tests {
     use super::*;
 
@@ -195,8 +513,121 @@ mod tests {
     fn sanity_check_mlp_forward() {
         let input = vec![Value::from(2.0); 2];
         let mlp = MLP::new(vec![2, 3, 1]);
-        let output = mlp.forward(input);
+        let output = mlp.forward(&input);
         assert_eq!(output.len(), 1);
         println!("{:?}", output)
     }
+
+    #[test]
+    fn mlp_of_one_size_has_no_layers() {
+        let mlp = MLP::new(vec![3]);
+        assert!(mlp.layers.is_empty());
+        assert!(mlp.parameters().is_empty());
+    }
+
+    #[test]
+    fn mlp_builder_sets_activations() {
+        let mlp = MLP::builder(3)
+            .layer(4, Activation::Tanh)
+            .layer(4, Activation::LeakyRelu(0.1))
+            .layer(1, Activation::Sigmoid)
+            .build();
+        let activations: Vec<Activation> = mlp.layers.iter().map(Layer::activation).collect();
+        assert_eq!(
+            activations,
+            vec![
+                Activation::Tanh,
+                Activation::LeakyRelu(0.1),
+                Activation::Sigmoid
+            ]
+        );
+        assert_eq!(mlp.parameters().len(), 4 * 4 + 4 * 5 + 5);
+        let output = mlp.forward(&[Value::from(1.0), Value::from(1.0), Value::from(1.0)])[0].data();
+        assert!(output > 0.0 && output < 1.0);
+
+        let default: Vec<Activation> = MLP::new(vec![2, 3, 3, 1])
+            .layers
+            .iter()
+            .map(Layer::activation)
+            .collect();
+        assert_eq!(
+            default,
+            vec![Activation::Relu, Activation::Relu, Activation::Identity]
+        );
+    }
+
+    #[test]
+    fn mlp_builder_initializes_per_layer() {
+        let build = |seed| {
+            MLP::builder(4)
+                .with_init(Init::HeNormal)
+                .layer(8, Activation::Relu)
+                .layer_with_init(1, Activation::Identity, Init::Constant(0.5))
+                .with_seed(seed)
+                .build()
+        };
+        let data = |mlp: &MLP| mlp.parameters().iter().map(Value::data).collect::<Vec<_>>();
+        let mlp = build(1);
+        assert_eq!(data(&mlp), data(&build(1)));
+        assert_ne!(data(&mlp), data(&build(2)));
+        assert_eq!(mlp.last_layer(), mlp.layers[1].parameters());
+        let last: Vec<f64> = mlp.last_layer().iter().map(Value::data).collect();
+        assert_eq!(last, [vec![0.0], vec![0.5; 8]].concat());
+    }
+
+    #[test]
+    fn dropout_is_active_only_in_training() {
+        let mut mlp = MLP::builder(4)
+            .layer(64, Activation::Relu)
+            .dropout(0.5)
+            .layer(1, Activation::Identity)
+            .with_seed(3)
+            .build();
+        let input = vec![Value::from(1.0); 4];
+        let output = |mlp: &MLP| mlp.forward(&input)[0].data();
+
+        assert!(!mlp.is_training());
+        let evaluated = output(&mlp);
+        assert_eq!(output(&mlp), evaluated);
+
+        mlp.train();
+        assert!(mlp.is_training() && mlp.layers.iter().all(Layer::is_training));
+        let trained = [output(&mlp), output(&mlp)];
+        assert_ne!(trained[0], trained[1]);
+        assert_ne!(trained[0], evaluated);
+
+        mlp.eval();
+        assert_eq!(output(&mlp), evaluated);
+        assert_eq!(
+            mlp.layers[0].to_string(),
+            "Layer(4 -> 64, relu, dropout 0.5)"
+        );
+
+        let without = MLP::builder(4)
+            .layer(64, Activation::Relu)
+            .layer(1, Activation::Identity)
+            .with_seed(3)
+            .build();
+        assert_eq!(output(&without), evaluated);
+    }
+
+    #[test]
+    fn summary_and_display() {
+        let mlp = MLP::builder(2)
+            .layer(16, Activation::LeakyRelu(0.1))
+            .dropout(0.25)
+            .layer(1, Activation::Identity)
+            .build();
+        assert_eq!(mlp.to_string(), "MLP(2 -> 16 -> 1, 65 parameters)");
+        assert_eq!(mlp.layers[1].to_string(), "Layer(16 -> 1, identity)");
+        let expected = "\
+layer  input  output  activation       dropout  parameters
+----------------------------------------------------------
+0          2      16  leaky_relu(0.1)     0.25          48
+1         16       1  identity               -          17
+----------------------------------------------------------
+total      2       1                                    65
+";
+        assert_eq!(mlp.summary(), expected);
+    }
 }
@@ -0,0 +1,330 @@
+use std::{cell::RefCell, rc::Rc};
+
+use super::{Buffer, Module, Value};
+
+const EPS: f64 = 1e-5;
+
+/// Normalizes each feature over the batch, then scales and shifts it by learned parameters.
+///
+/// While training, the batch mean and variance are `Value`s, so gradients flow through them,
+/// and running averages of both are kept. In evaluation mode the running averages are used
+/// instead, so a row's output no longer depends on the rest of its batch. Training a single
+/// row with [`Module::forward`] treats it as a batch of one.
+#[derive(Debug)]
+pub struct BatchNorm1d {
+    weight: Vec<Value>,
+    bias: Vec<Value>,
+    running_mean: Rc<RefCell<Vec<f64>>>,
+    running_var: Rc<RefCell<Vec<f64>>>,
+    momentum: f64,
+    eps: f64,
+    training: bool,
+}
+
+impl BatchNorm1d {
+    pub fn new(features: usize) -> BatchNorm1d {
+        BatchNorm1d {
+            weight: (0..features).map(|_| Value::from(1.0)).collect(),
+            bias: (0..features).map(|_| Value::from(0.0)).collect(),
+            running_mean: Rc::new(RefCell::new(vec![0.0; features])),
+            running_var: Rc::new(RefCell::new(vec![1.0; features])),
+            momentum: 0.1,
+            eps: EPS,
+            training: false,
+        }
+    }
+
+    /// Weight of each new batch in the running statistics, 0.1 unless set.
+    pub fn with_momentum(mut self, momentum: f64) -> BatchNorm1d {
+        self.momentum = momentum;
+        self
+    }
+
+    pub fn with_eps(mut self, eps: f64) -> BatchNorm1d {
+        self.eps = eps;
+        self
+    }
+
+    pub fn running_mean(&self) -> Vec<f64> {
+        self.running_mean.borrow().clone()
+    }
+
+    /// Running average of the unbiased batch variance.
+    pub fn running_var(&self) -> Vec<f64> {
+        self.running_var.borrow().clone()
+    }
+
+    fn normalize_batch(&self, inputs: &[Vec<Value>]) -> Vec<Vec<Value>> {
+        let n = inputs.len();
+        let mut outputs = vec![Vec::with_capacity(self.weight.len()); n];
+        let mut running_mean = self.running_mean.borrow_mut();
+        let mut running_var = self.running_var.borrow_mut();
+        for j in 0..self.weight.len() {
+            let column: Vec<&Value> = inputs.iter().map(|row| &row[j]).collect();
+            let (mean, var) = moments(&column);
+            let std = (&var + &Value::from(self.eps)).pow(0.5);
+            for (out, x) in outputs.iter_mut().zip(&column) {
+                out.push(&(&(&(*x - &mean) / &std) * &self.weight[j]) + &self.bias[j]);
+            }
+
+            let unbiased = if n > 1 {
+                var.data() * n as f64 / (n - 1) as f64
+            } else {
+                var.data()
+            };
+            running_mean[j] += self.momentum * (mean.data() - running_mean[j]);
+            running_var[j] += self.momentum * (unbiased - running_var[j]);
+        }
+        outputs
+    }
+}
+
+impl Module for BatchNorm1d {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        assert_eq!(
+            input.len(),
+            self.weight.len(),
+            "input must have one value per feature"
+        );
+        if self.training {
+            return self.normalize_batch(&[input.to_vec()]).remove(0);
+        }
+        let running_mean = self.running_mean.borrow();
+        let running_var = self.running_var.borrow();
+        input
+            .iter()
+            .enumerate()
+            .map(|(j, x)| {
+                let centered = x - &Value::from(running_mean[j]);
+                let std = Value::from((running_var[j] + self.eps).sqrt());
+                &(&(&centered / &std) * &self.weight[j]) + &self.bias[j]
+            })
+            .collect()
+    }
+
+    fn forward_batch(&self, inputs: &[Vec<Value>]) -> Vec<Vec<Value>> {
+        if !self.training || inputs.is_empty() {
+            return inputs.iter().map(|input| self.forward(input)).collect();
+        }
+        assert!(
+            inputs.iter().all(|row| row.len() == self.weight.len()),
+            "input must have one value per feature"
+        );
+        self.normalize_batch(inputs)
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        named_gain_and_bias(&self.weight, &self.bias)
+    }
+
+    fn named_buffers(&self) -> Vec<(String, Buffer)> {
+        vec![
+            (
+                "running_mean".to_string(),
+                Buffer::Values(self.running_mean.clone()),
+            ),
+            (
+                "running_var".to_string(),
+                Buffer::Values(self.running_var.clone()),
+            ),
+        ]
+    }
+
+    fn set_training(&mut self, training: bool) {
+        self.training = training;
+    }
+
+    fn is_training(&self) -> bool {
+        self.training
+    }
+}
+
+/// Normalizes each row over its own features, then scales and shifts each feature by learned
+/// parameters. It behaves the same in training and evaluation.
+#[derive(Debug)]
+pub struct LayerNorm {
+    weight: Vec<Value>,
+    bias: Vec<Value>,
+    eps: f64,
+}
+
+impl LayerNorm {
+    pub fn new(features: usize) -> LayerNorm {
+        LayerNorm {
+            weight: (0..features).map(|_| Value::from(1.0)).collect(),
+            bias: (0..features).map(|_| Value::from(0.0)).collect(),
+            eps: EPS,
+        }
+    }
+
+    pub fn with_eps(mut self, eps: f64) -> LayerNorm {
+        self.eps = eps;
+        self
+    }
+}
+
+impl Module for LayerNorm {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        assert_eq!(
+            input.len(),
+            self.weight.len(),
+            "input must have one value per feature"
+        );
+        let (mean, var) = moments(&input.iter().collect::<Vec<_>>());
+        let std = (&var + &Value::from(self.eps)).pow(0.5);
+        input
+            .iter()
+            .zip(self.weight.iter().zip(&self.bias))
+            .map(|(x, (weight, bias))| &(&(&(x - &mean) / &std) * weight) + bias)
+            .collect()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        named_gain_and_bias(&self.weight, &self.bias)
+    }
+}
+
+/// The mean and biased variance of `xs`.
+fn moments(xs: &[&Value]) -> (Value, Value) {
+    let n = Value::from(xs.len() as f64);
+    let mean = &xs.iter().copied().cloned().sum::<Value>() / &n;
+    let var = &xs.iter().map(|x| (*x - &mean).pow(2.0)).sum::<Value>() / &n;
+    (mean, var)
+}
+
+fn named_gain_and_bias(weight: &[Value], bias: &[Value]) -> Vec<(String, Value)> {
+    let weights = weight
+        .iter()
+        .enumerate()
+        .map(|(j, w)| (format!("weight.{j}"), w.clone()));
+    let biases = bias
+        .iter()
+        .enumerate()
+        .map(|(j, b)| (format!("bias.{j}"), b.clone()));
+    weights.chain(biases).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micrograd::Sequential;
+
+    /// Compares backprop gradients of every leaf against central differences, shifting each
+    /// leaf's data in place and rebuilding the graph with `loss`.
+    fn gradcheck(leaves: &[Value], loss: impl Fn() -> Value) {
+        loss().backward();
+        let h = 1e-6;
+        for (i, leaf) in leaves.iter().enumerate() {
+            let original = leaf.data();
+            let at = |x: f64| {
+                leaf.borrow_mut().data = x;
+                loss().data()
+            };
+            let numeric = (at(original + h) - at(original - h)) / (2.0 * h);
+            leaf.borrow_mut().data = original;
+            assert!(
+                (leaf.grad() - numeric).abs() < 1e-5,
+                "leaf {i}: backprop {} vs numeric {numeric}",
+                leaf.grad()
+            );
+        }
+    }
+
+    /// A loss mixing the outputs with distinct weights, since their plain sum is constant.
+    fn mix(outputs: &[Vec<Value>]) -> Value {
+        outputs
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(k, out)| out * &Value::from(0.3 * k as f64 - 1.0))
+            .sum()
+    }
+
+    fn batch() -> Vec<Vec<Value>> {
+        [
+            [0.5, -1.2, 3.0],
+            [1.5, 0.4, -2.0],
+            [-0.7, 2.2, 0.1],
+            [0.3, 0.0, 1.4],
+        ]
+        .iter()
+        .map(|row| row.iter().map(|&x| Value::from(x)).collect())
+        .collect()
+    }
+
+    #[test]
+    fn batch_norm_normalizes_and_tracks_statistics() {
+        let mut norm = BatchNorm1d::new(3).with_momentum(0.5);
+        norm.train();
+        let outputs = norm.forward_batch(&batch());
+        for j in 0..3 {
+            let column: Vec<f64> = outputs.iter().map(|row| row[j].data()).collect();
+            let mean = column.iter().sum::<f64>() / 4.0;
+            let var = column.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / 4.0;
+            assert!(mean.abs() < 1e-12);
+            assert!((var - 1.0).abs() < 1e-4);
+        }
+        // halfway from the initial statistics to the batch mean and unbiased variance
+        assert!((norm.running_mean()[0] - 0.2).abs() < 1e-12);
+        assert!((norm.running_var()[0] - (0.5 + 0.5 * 2.44 / 3.0)).abs() < 1e-12);
+
+        norm.eval();
+        let row = vec![Value::from(0.2), Value::from(0.0), Value::from(0.0)];
+        let data = |row: &[Value]| row.iter().map(Value::data).collect::<Vec<_>>();
+        let batched = norm.forward_batch(std::slice::from_ref(&row));
+        assert_eq!(data(&batched[0]), data(&norm.forward(&row)));
+        assert!(norm.forward(&row)[0].data().abs() < 1e-12);
+    }
+
+    #[test]
+    fn batch_norm_gradients_match_finite_differences() {
+        let mut norm = BatchNorm1d::new(3);
+        norm.train();
+        let inputs = batch();
+        for (w, b) in norm.weight.iter().zip(&norm.bias) {
+            w.borrow_mut().data = 1.5;
+            b.borrow_mut().data = -0.5;
+        }
+        let leaves: Vec<Value> = inputs
+            .iter()
+            .flatten()
+            .cloned()
+            .chain(norm.parameters())
+            .collect();
+        gradcheck(&leaves, || mix(&norm.forward_batch(&inputs)));
+    }
+
+    #[test]
+    fn sequential_normalizes_whole_batches() {
+        let mut model = Sequential::new()
+            .with_module(LayerNorm::new(3))
+            .with_module(BatchNorm1d::new(3));
+        model.train();
+        let outputs = model.forward_batch(&batch());
+        let column: Vec<f64> = outputs.iter().map(|row| row[1].data()).collect();
+        assert!(column.iter().sum::<f64>().abs() < 1e-12);
+        assert_eq!(model.parameters().len(), 12);
+    }
+
+    #[test]
+    fn layer_norm_gradients_match_finite_differences() {
+        let norm = LayerNorm::new(3);
+        let inputs = batch();
+        let outputs = norm.forward_batch(&inputs);
+        for row in &outputs {
+            let mean = row.iter().map(Value::data).sum::<f64>() / 3.0;
+            assert!(mean.abs() < 1e-12);
+        }
+        assert_eq!(norm.named_parameters()[3].0, "bias.0");
+
+        norm.weight[1].borrow_mut().data = 2.0;
+        norm.bias[2].borrow_mut().data = 0.7;
+        let leaves: Vec<Value> = inputs
+            .iter()
+            .flatten()
+            .cloned()
+            .chain(norm.parameters())
+            .collect();
+        gradcheck(&leaves, || mix(&norm.forward_batch(&inputs)));
+    }
+}
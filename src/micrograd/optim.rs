@@ -0,0 +1,598 @@
+use std::collections::BTreeMap;
+
+use super::{regularize::flatten, ParamGroup, Regularization, Value};
+use crate::Result;
+
+/// What an optimizer's next step depends on besides the parameters, e.g. the learning rate
+/// and momentum buffers, by name.
+pub type OptimizerState = BTreeMap<String, Vec<f64>>;
+
+/// Updates a fixed set of parameters from their gradients, keeping any per-parameter state.
+pub trait Optimizer {
+    fn parameters(&self) -> &[Value];
+    fn learning_rate(&self) -> f64;
+    fn set_learning_rate(&mut self, learning_rate: f64);
+    /// Applies one update using the gradients currently stored in the parameters.
+    fn step(&mut self);
+
+    /// The regularization of each parameter, empty if the optimizer has none.
+    fn regularization(&self) -> &[Regularization] {
+        &[]
+    }
+
+    /// The total penalty the regularization adds to the loss, for reporting.
+    fn penalty(&self) -> f64 {
+        self.parameters()
+            .iter()
+            .zip(self.regularization())
+            .map(|(p, regularization)| regularization.penalty(p.data()))
+            .sum()
+    }
+
+    /// A copy of the state, enough to continue exactly where this optimizer is.
+    fn state_dict(&self) -> OptimizerState {
+        OptimizerState::from([("learning_rate".to_string(), vec![self.learning_rate()])])
+    }
+
+    /// Restores a state saved by `state_dict` of the same kind of optimizer over the same
+    /// parameters.
+    fn load_state_dict(&mut self, state: &OptimizerState) -> Result<()> {
+        self.set_learning_rate(entry(state, "learning_rate", 1)?[0]);
+        Ok(())
+    }
+
+    fn zero_grad(&self) {
+        for p in self.parameters() {
+            p.borrow_mut().grad = 0.0;
+        }
+    }
+}
+
+/// The values saved under `name`, which must be `len` long.
+fn entry<'a>(state: &'a OptimizerState, name: &str, len: usize) -> Result<&'a [f64]> {
+    match state.get(name) {
+        Some(values) if values.len() == len => Ok(values),
+        Some(values) => Err(format!(
+            "optimizer state {name} has {} values, expected {len}",
+            values.len()
+        )
+        .into()),
+        None => Err(format!("optimizer state has no {name}").into()),
+    }
+}
+
+/// Stochastic gradient descent, optionally with (Nesterov) momentum.
+#[derive(Debug)]
+pub struct SGD {
+    parameters: Vec<Value>,
+    regularization: Vec<Regularization>,
+    learning_rate: f64,
+    momentum: f64,
+    nesterov: bool,
+    velocity: Vec<f64>,
+}
+
+impl SGD {
+    pub fn new(parameters: Vec<Value>, learning_rate: f64) -> SGD {
+        let velocity = vec![0.0; parameters.len()];
+        SGD {
+            regularization: vec![Regularization::None; parameters.len()],
+            parameters,
+            learning_rate,
+            momentum: 0.0,
+            nesterov: false,
+            velocity,
+        }
+    }
+
+    /// Optimizes the parameters of every group, each regularized as its group says.
+    pub fn from_groups(groups: Vec<ParamGroup>, learning_rate: f64) -> SGD {
+        let (parameters, regularization) = flatten(groups);
+        SGD {
+            regularization,
+            ..SGD::new(parameters, learning_rate)
+        }
+    }
+
+    /// Regularizes every parameter the same way.
+    pub fn with_regularization(mut self, regularization: Regularization) -> SGD {
+        self.regularization.fill(regularization);
+        self
+    }
+
+    pub fn with_momentum(mut self, momentum: f64) -> SGD {
+        self.momentum = momentum;
+        self
+    }
+
+    /// Uses Nesterov momentum, which looks ahead along the velocity before stepping.
+    pub fn with_nesterov(mut self, momentum: f64) -> SGD {
+        self.momentum = momentum;
+        self.nesterov = true;
+        self
+    }
+}
+
+impl Optimizer for SGD {
+    fn parameters(&self) -> &[Value] {
+        &self.parameters
+    }
+
+    fn learning_rate(&self) -> f64 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn regularization(&self) -> &[Regularization] {
+        &self.regularization
+    }
+
+    fn state_dict(&self) -> OptimizerState {
+        OptimizerState::from([
+            ("learning_rate".to_string(), vec![self.learning_rate]),
+            ("velocity".to_string(), self.velocity.clone()),
+        ])
+    }
+
+    fn load_state_dict(&mut self, state: &OptimizerState) -> Result<()> {
+        let learning_rate = entry(state, "learning_rate", 1)?[0];
+        let velocity = entry(state, "velocity", self.parameters.len())?;
+        self.learning_rate = learning_rate;
+        self.velocity = velocity.to_vec();
+        Ok(())
+    }
+
+    fn step(&mut self) {
+        for ((p, v), regularization) in self
+            .parameters
+            .iter()
+            .zip(self.velocity.iter_mut())
+            .zip(&self.regularization)
+        {
+            let grad = regularization.apply(p, self.learning_rate);
+            *v = self.momentum * *v + grad;
+            let update = if self.nesterov {
+                grad + self.momentum * *v
+            } else {
+                *v
+            };
+            p.borrow_mut().data -= self.learning_rate * update;
+        }
+    }
+}
+
+/// Adam, with bias-corrected first and second moment estimates.
+#[derive(Debug)]
+pub struct Adam {
+    parameters: Vec<Value>,
+    regularization: Vec<Regularization>,
+    learning_rate: f64,
+    beta1: f64,
+    beta2: f64,
+    eps: f64,
+    steps: usize,
+    m: Vec<f64>,
+    v: Vec<f64>,
+}
+
+impl Adam {
+    pub fn new(parameters: Vec<Value>, learning_rate: f64) -> Adam {
+        let n = parameters.len();
+        Adam {
+            parameters,
+            regularization: vec![Regularization::None; n],
+            learning_rate,
+            beta1: 0.9,
+            beta2: 0.999,
+            eps: 1e-8,
+            steps: 0,
+            m: vec![0.0; n],
+            v: vec![0.0; n],
+        }
+    }
+
+    /// Optimizes the parameters of every group, each regularized as its group says.
+    pub fn from_groups(groups: Vec<ParamGroup>, learning_rate: f64) -> Adam {
+        let (parameters, regularization) = flatten(groups);
+        Adam {
+            regularization,
+            ..Adam::new(parameters, learning_rate)
+        }
+    }
+
+    /// Regularizes every parameter the same way.
+    pub fn with_regularization(mut self, regularization: Regularization) -> Adam {
+        self.regularization.fill(regularization);
+        self
+    }
+
+    pub fn with_betas(mut self, beta1: f64, beta2: f64) -> Adam {
+        self.beta1 = beta1;
+        self.beta2 = beta2;
+        self
+    }
+
+    pub fn with_eps(mut self, eps: f64) -> Adam {
+        self.eps = eps;
+        self
+    }
+}
+
+impl Optimizer for Adam {
+    fn parameters(&self) -> &[Value] {
+        &self.parameters
+    }
+
+    fn learning_rate(&self) -> f64 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn regularization(&self) -> &[Regularization] {
+        &self.regularization
+    }
+
+    fn state_dict(&self) -> OptimizerState {
+        OptimizerState::from([
+            ("learning_rate".to_string(), vec![self.learning_rate]),
+            ("steps".to_string(), vec![self.steps as f64]),
+            ("m".to_string(), self.m.clone()),
+            ("v".to_string(), self.v.clone()),
+        ])
+    }
+
+    fn load_state_dict(&mut self, state: &OptimizerState) -> Result<()> {
+        let n = self.parameters.len();
+        let learning_rate = entry(state, "learning_rate", 1)?[0];
+        let steps = entry(state, "steps", 1)?[0];
+        if steps < 0.0 || steps.fract() != 0.0 {
+            return Err(format!("optimizer state steps is {steps}, not a step count").into());
+        }
+        let (m, v) = (entry(state, "m", n)?, entry(state, "v", n)?);
+        self.learning_rate = learning_rate;
+        self.steps = steps as usize;
+        self.m = m.to_vec();
+        self.v = v.to_vec();
+        Ok(())
+    }
+
+    fn step(&mut self) {
+        self.steps += 1;
+        let correction1 = 1.0 - self.beta1.powf(self.steps as f64);
+        let correction2 = 1.0 - self.beta2.powf(self.steps as f64);
+        for (((p, m), v), regularization) in self
+            .parameters
+            .iter()
+            .zip(self.m.iter_mut())
+            .zip(self.v.iter_mut())
+            .zip(&self.regularization)
+        {
+            let grad = regularization.apply(p, self.learning_rate);
+            *m = self.beta1 * *m + (1.0 - self.beta1) * grad;
+            *v = self.beta2 * *v + (1.0 - self.beta2) * grad * grad;
+            let m_hat = *m / correction1;
+            let v_hat = *v / correction2;
+            p.borrow_mut().data -= self.learning_rate * m_hat / (v_hat.sqrt() + self.eps);
+        }
+    }
+}
+
+/// Adam with decoupled weight decay: parameters shrink by `learning_rate * weight_decay`
+/// every step, independently of the gradient moments.
+///
+/// This is `Adam` with [`Regularization::WeightDecay`] on every parameter; use
+/// [`Adam::from_groups`] to decay only some of them.
+#[derive(Debug)]
+pub struct AdamW {
+    adam: Adam,
+}
+
+impl AdamW {
+    pub fn new(parameters: Vec<Value>, learning_rate: f64, weight_decay: f64) -> AdamW {
+        AdamW {
+            adam: Adam::new(parameters, learning_rate)
+                .with_regularization(Regularization::WeightDecay(weight_decay)),
+        }
+    }
+
+    pub fn with_betas(mut self, beta1: f64, beta2: f64) -> AdamW {
+        self.adam = self.adam.with_betas(beta1, beta2);
+        self
+    }
+
+    pub fn with_eps(mut self, eps: f64) -> AdamW {
+        self.adam = self.adam.with_eps(eps);
+        self
+    }
+}
+
+impl Optimizer for AdamW {
+    fn parameters(&self) -> &[Value] {
+        self.adam.parameters()
+    }
+
+    fn learning_rate(&self) -> f64 {
+        self.adam.learning_rate()
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.adam.set_learning_rate(learning_rate);
+    }
+
+    fn regularization(&self) -> &[Regularization] {
+        self.adam.regularization()
+    }
+
+    fn state_dict(&self) -> OptimizerState {
+        self.adam.state_dict()
+    }
+
+    fn load_state_dict(&mut self, state: &OptimizerState) -> Result<()> {
+        self.adam.load_state_dict(state)
+    }
+
+    fn step(&mut self) {
+        self.adam.step();
+    }
+}
+
+/// RMSProp, scaling each step by a running average of squared gradients.
+#[derive(Debug)]
+pub struct RMSProp {
+    parameters: Vec<Value>,
+    regularization: Vec<Regularization>,
+    learning_rate: f64,
+    alpha: f64,
+    eps: f64,
+    square_avg: Vec<f64>,
+}
+
+impl RMSProp {
+    pub fn new(parameters: Vec<Value>, learning_rate: f64) -> RMSProp {
+        let square_avg = vec![0.0; parameters.len()];
+        RMSProp {
+            regularization: vec![Regularization::None; parameters.len()],
+            parameters,
+            learning_rate,
+            alpha: 0.99,
+            eps: 1e-8,
+            square_avg,
+        }
+    }
+
+    /// Optimizes the parameters of every group, each regularized as its group says.
+    pub fn from_groups(groups: Vec<ParamGroup>, learning_rate: f64) -> RMSProp {
+        let (parameters, regularization) = flatten(groups);
+        RMSProp {
+            regularization,
+            ..RMSProp::new(parameters, learning_rate)
+        }
+    }
+
+    /// Regularizes every parameter the same way.
+    pub fn with_regularization(mut self, regularization: Regularization) -> RMSProp {
+        self.regularization.fill(regularization);
+        self
+    }
+
+    /// Smoothing constant of the squared-gradient average.
+    pub fn with_alpha(mut self, alpha: f64) -> RMSProp {
+        self.alpha = alpha;
+        self
+    }
+
+    pub fn with_eps(mut self, eps: f64) -> RMSProp {
+        self.eps = eps;
+        self
+    }
+}
+
+impl Optimizer for RMSProp {
+    fn parameters(&self) -> &[Value] {
+        &self.parameters
+    }
+
+    fn learning_rate(&self) -> f64 {
+        self.learning_rate
+    }
+
+    fn set_learning_rate(&mut self, learning_rate: f64) {
+        self.learning_rate = learning_rate;
+    }
+
+    fn regularization(&self) -> &[Regularization] {
+        &self.regularization
+    }
+
+    fn state_dict(&self) -> OptimizerState {
+        OptimizerState::from([
+            ("learning_rate".to_string(), vec![self.learning_rate]),
+            ("square_avg".to_string(), self.square_avg.clone()),
+        ])
+    }
+
+    fn load_state_dict(&mut self, state: &OptimizerState) -> Result<()> {
+        let learning_rate = entry(state, "learning_rate", 1)?[0];
+        let square_avg = entry(state, "square_avg", self.parameters.len())?;
+        self.learning_rate = learning_rate;
+        self.square_avg = square_avg.to_vec();
+        Ok(())
+    }
+
+    fn step(&mut self) {
+        for ((p, avg), regularization) in self
+            .parameters
+            .iter()
+            .zip(self.square_avg.iter_mut())
+            .zip(&self.regularization)
+        {
+            let grad = regularization.apply(p, self.learning_rate);
+            *avg = self.alpha * *avg + (1.0 - self.alpha) * grad * grad;
+            p.borrow_mut().data -= self.learning_rate * grad / (avg.sqrt() + self.eps);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: [f64; 3] = [1.0, -2.0, 0.5];
+    const SCALE: [f64; 3] = [1.0, 3.0, 0.5];
+
+    /// sum_i scale_i * (x_i - target_i)^2, minimised at `TARGET`
+    fn quadratic(x: &[Value]) -> Value {
+        x.iter()
+            .zip(TARGET.iter().zip(SCALE))
+            .map(|(xi, (t, s))| &(xi - &Value::from(*t)).pow(2.0) * &Value::from(s))
+            .sum()
+    }
+
+    fn minimise(optimizer: &mut dyn Optimizer, steps: usize) -> Vec<f64> {
+        for _ in 0..steps {
+            optimizer.zero_grad();
+            quadratic(optimizer.parameters()).backward();
+            optimizer.step();
+        }
+        optimizer.parameters().iter().map(Value::data).collect()
+    }
+
+    fn start() -> Vec<Value> {
+        (0..3).map(|_| Value::from(4.0)).collect()
+    }
+
+    fn assert_converged(x: &[f64], tolerance: f64) {
+        x.iter()
+            .zip(TARGET)
+            .for_each(|(xi, t)| assert!((xi - t).abs() < tolerance, "{x:?}"));
+    }
+
+    #[test]
+    fn sgd_single_step() {
+        let p = Value::from(2.0);
+        let mut sgd = SGD::new(vec![p.clone()], 0.1);
+        p.pow(2.0).backward();
+        sgd.step();
+        assert!((p.data() - 1.6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sgd_momentum_accumulates_velocity() {
+        let p = Value::from(0.0);
+        let mut sgd = SGD::new(vec![p.clone()], 1.0).with_momentum(0.5);
+        for _ in 0..2 {
+            sgd.zero_grad();
+            p.borrow_mut().grad = 1.0;
+            sgd.step();
+        }
+        // velocities 1.0 then 1.5
+        assert_eq!(p.data(), -2.5);
+    }
+
+    #[test]
+    fn sgd_converges() {
+        assert_converged(&minimise(&mut SGD::new(start(), 0.05), 500), 1e-6);
+        let mut momentum = SGD::new(start(), 0.02).with_momentum(0.9);
+        assert_converged(&minimise(&mut momentum, 300), 1e-4);
+        let mut nesterov = SGD::new(start(), 0.02).with_nesterov(0.9);
+        assert_converged(&minimise(&mut nesterov, 300), 1e-4);
+    }
+
+    #[test]
+    fn adam_converges() {
+        assert_converged(&minimise(&mut Adam::new(start(), 0.1), 500), 1e-3);
+    }
+
+    #[test]
+    fn adamw_decays_towards_zero() {
+        // with decoupled decay the optimum is pulled slightly towards the origin
+        let x = minimise(&mut AdamW::new(start(), 0.05, 0.1), 1000);
+        assert_converged(&x, 0.2);
+        x.iter()
+            .zip(TARGET)
+            .for_each(|(xi, t)| assert!(xi.abs() < t.abs()));
+    }
+
+    #[test]
+    fn penalties_shift_the_optimum_per_group() {
+        // minimizing scale * (x - t)^2 + alpha * x^2 gives x = scale * t / (scale + alpha)
+        let x = start();
+        let groups = vec![
+            ParamGroup::new(vec![x[0].clone()], Regularization::L2(1.0)),
+            ParamGroup::new(vec![x[1].clone()], Regularization::None),
+            ParamGroup::new(vec![x[2].clone()], Regularization::L1(0.5)),
+        ];
+        let mut sgd = SGD::from_groups(groups, 0.05);
+        for _ in 0..500 {
+            sgd.zero_grad();
+            quadratic(&x).backward();
+            sgd.step();
+        }
+        assert!((x[0].data() - 0.5).abs() < 1e-6);
+        assert!((x[1].data() + 2.0).abs() < 1e-6);
+        // the L1 slope pulls 0.5 * (x - 0.5)^2 to its minimum at x = 0
+        assert!(x[2].data().abs() < 0.05);
+        assert!((sgd.penalty() - 1.0 * 0.25 - 0.5 * x[2].data().abs()).abs() < 1e-6);
+
+        let mut elastic = Adam::new(start(), 0.05)
+            .with_regularization(Regularization::ElasticNet { l1: 0.1, l2: 0.1 });
+        let shrunk = minimise(&mut elastic, 1000);
+        shrunk
+            .iter()
+            .zip(TARGET)
+            .for_each(|(xi, t)| assert!(xi.abs() < t.abs() && xi * t > 0.0));
+        assert!(elastic.penalty() > 0.0);
+        assert_eq!(RMSProp::new(start(), 0.01).penalty(), 0.0);
+    }
+
+    #[test]
+    fn state_dict_continues_exactly() {
+        type Make = fn(Vec<Value>) -> Box<dyn Optimizer>;
+        let optimizers: [Make; 4] = [
+            |p| Box::new(SGD::new(p, 0.02).with_momentum(0.9)),
+            |p| Box::new(Adam::new(p, 0.1)),
+            |p| Box::new(AdamW::new(p, 0.1, 0.1)),
+            |p| Box::new(RMSProp::new(p, 0.01)),
+        ];
+        for make in optimizers {
+            let mut straight = make(start());
+            let expected = minimise(straight.as_mut(), 20);
+
+            let mut first = make(start());
+            let halfway = minimise(first.as_mut(), 10);
+            let parameters: Vec<Value> = halfway.into_iter().map(Value::from).collect();
+            let mut second = make(parameters);
+            let state = first.state_dict();
+            let fresh = second.state_dict();
+            // a bad entry anywhere leaves the optimizer as it was
+            for name in state.keys() {
+                let mut broken = state.clone();
+                broken.get_mut(name).unwrap().push(0.0);
+                assert!(second.load_state_dict(&broken).is_err());
+                assert_eq!(second.state_dict(), fresh, "{name}");
+            }
+            second.load_state_dict(&state).unwrap();
+            assert_eq!(minimise(second.as_mut(), 10), expected);
+        }
+
+        let mut sgd = SGD::new(start(), 0.1);
+        let mut state = Adam::new(start(), 0.1).state_dict();
+        assert!(sgd.load_state_dict(&state).is_err());
+        state.insert("velocity".to_string(), vec![0.0; 2]);
+        assert!(sgd.load_state_dict(&state).is_err());
+        let mut adam = Adam::new(start(), 0.1);
+        state.insert("steps".to_string(), vec![-1.0]);
+        assert!(adam.load_state_dict(&state).is_err());
+    }
+
+    #[test]
+    fn rmsprop_converges() {
+        assert_converged(&minimise(&mut RMSProp::new(start(), 0.01), 1000), 1e-2);
+    }
+}
@@ -0,0 +1,155 @@
+use super::Value;
+
+/// Pulls parameters towards zero inside an optimizer's update.
+///
+/// Penalties add their gradient to the parameter's before the update, exactly as if the
+/// penalty had been added to the loss, but without building graph nodes for it. Weight decay
+/// instead shrinks the parameter by `learning_rate * decay` on every step, decoupled from the
+/// gradient as in AdamW.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Regularization {
+    #[default]
+    None,
+    /// Penalty `alpha * |p|`.
+    L1(f64),
+    /// Penalty `alpha * p^2`.
+    L2(f64),
+    /// Penalty `l1 * |p| + l2 * p^2`.
+    ElasticNet {
+        l1: f64,
+        l2: f64,
+    },
+    WeightDecay(f64),
+}
+
+impl Regularization {
+    /// The penalty `p` adds to the loss, 0 for weight decay.
+    pub fn penalty(&self, p: f64) -> f64 {
+        let (l1, l2) = self.coefficients();
+        l1 * p.abs() + l2 * p * p
+    }
+
+    /// The penalty's derivative, 0 at `p = 0` for L1.
+    pub fn gradient(&self, p: f64) -> f64 {
+        let (l1, l2) = self.coefficients();
+        let sign = if p == 0.0 { 0.0 } else { p.signum() };
+        l1 * sign + 2.0 * l2 * p
+    }
+
+    fn coefficients(&self) -> (f64, f64) {
+        match *self {
+            Regularization::L1(alpha) => (alpha, 0.0),
+            Regularization::L2(alpha) => (0.0, alpha),
+            Regularization::ElasticNet { l1, l2 } => (l1, l2),
+            Regularization::None | Regularization::WeightDecay(_) => (0.0, 0.0),
+        }
+    }
+
+    /// Applies any weight decay to `p` and returns its gradient plus the penalty's.
+    pub(crate) fn apply(&self, p: &Value, learning_rate: f64) -> f64 {
+        if let Regularization::WeightDecay(decay) = *self {
+            p.borrow_mut().data *= 1.0 - learning_rate * decay;
+        }
+        let p = p.borrow();
+        p.grad + self.gradient(p.data)
+    }
+}
+
+/// Parameters that share a regularization.
+#[derive(Debug, Clone)]
+pub struct ParamGroup {
+    pub parameters: Vec<Value>,
+    pub regularization: Regularization,
+}
+
+impl ParamGroup {
+    pub fn new(parameters: Vec<Value>, regularization: Regularization) -> ParamGroup {
+        ParamGroup {
+            parameters,
+            regularization,
+        }
+    }
+}
+
+/// Groups named parameters by the regularization `rule` picks from each name, e.g. weights
+/// and biases, or each layer, of a [`Module`](super::Module).
+pub fn param_groups(
+    named: Vec<(String, Value)>,
+    rule: impl Fn(&str) -> Regularization,
+) -> Vec<ParamGroup> {
+    let mut groups: Vec<ParamGroup> = Vec::new();
+    for (name, p) in named {
+        let regularization = rule(&name);
+        match groups
+            .iter_mut()
+            .find(|group| group.regularization == regularization)
+        {
+            Some(group) => group.parameters.push(p),
+            None => groups.push(ParamGroup::new(vec![p], regularization)),
+        }
+    }
+    groups
+}
+
+/// The parameters of all groups and the regularization of each.
+pub(crate) fn flatten(groups: Vec<ParamGroup>) -> (Vec<Value>, Vec<Regularization>) {
+    groups
+        .into_iter()
+        .flat_map(|group| {
+            let regularization = group.regularization;
+            group
+                .parameters
+                .into_iter()
+                .map(move |p| (p, regularization))
+        })
+        .unzip()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micrograd::{Module, MLP};
+
+    #[test]
+    fn penalties_and_gradients() {
+        let elastic = Regularization::ElasticNet { l1: 0.5, l2: 0.25 };
+        assert_eq!(elastic.penalty(-2.0), 0.5 * 2.0 + 0.25 * 4.0);
+        assert_eq!(elastic.gradient(-2.0), -0.5 - 1.0);
+        assert_eq!(Regularization::L1(0.3).gradient(0.0), 0.0);
+        assert_eq!(Regularization::L2(0.25).gradient(3.0), 1.5);
+        assert_eq!(Regularization::WeightDecay(0.1).penalty(3.0), 0.0);
+
+        // the penalty gradient matches backprop through the penalty built as a graph
+        let p = Value::from(-1.5);
+        (&(&p.abs() * &Value::from(0.5)) + &(&p.pow(2.0) * &Value::from(0.25))).backward();
+        assert!((p.grad() - elastic.gradient(-1.5)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn apply_decays_and_adds_gradient() {
+        let p = Value::from(2.0);
+        p.borrow_mut().grad = 1.0;
+        assert_eq!(Regularization::WeightDecay(0.5).apply(&p, 0.1), 1.0);
+        assert!((p.data() - 1.9).abs() < 1e-12);
+        assert_eq!(Regularization::L2(0.5).apply(&p, 0.1), 1.0 + p.data());
+    }
+
+    #[test]
+    fn groups_follow_names() {
+        let model = MLP::new(vec![2, 3, 1]);
+        let groups = param_groups(model.named_parameters(), |name| {
+            match (name.starts_with("layers.0"), name.ends_with("bias")) {
+                (_, true) => Regularization::None,
+                (true, false) => Regularization::L1(0.01),
+                (false, false) => Regularization::L2(0.01),
+            }
+        });
+        let sizes: Vec<usize> = groups.iter().map(|g| g.parameters.len()).collect();
+        assert_eq!(sizes, [4, 6, 3]);
+        assert_eq!(groups[0].regularization, Regularization::None);
+
+        let (parameters, regularization) = flatten(groups);
+        assert_eq!(parameters.len(), 13);
+        assert_eq!(regularization[4], Regularization::L1(0.01));
+    }
+}
@@ -0,0 +1,269 @@
+//! Saving a training run so it can be resumed exactly.
+//!
+//! The checkpoint file format is little-endian binary:
+//!
+//! ```text
+//! magic          4 bytes  b"MGCK"
+//! version        u32      1
+//! epoch          u64      epochs completed
+//! step           u64      optimizer steps taken
+//! parameters     u32      count, each followed by
+//!   name                  u32 length and UTF-8 bytes
+//!   value        f64
+//! buffers        u32      count, each followed by
+//!   name
+//!   kind         u8       0 values, 1 random generator
+//!   values                u32 length and that many f64, or
+//!   generator             32 seed bytes, u64 stream, u128 word position
+//! optimizer      u32      count, each followed by
+//!   name
+//!   values                u32 length and that many f64
+//! callbacks      u32      count, each followed by
+//!   values                u32 length and that many f64
+//! shuffle        u8       1 if the data is shuffled, then its generator, 0 otherwise
+//! ```
+//!
+//! Random generators are stored by position rather than by seed, so a resumed run draws the
+//! same dropout masks and sample orders as an uninterrupted one would have.
+
+use std::{collections::BTreeMap, fs, path::Path};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use super::{serialize::Reader, Buffer, ModelFileError, OptimizerState, StateDict};
+use crate::Result;
+
+const MAGIC: &[u8; 4] = b"MGCK";
+const VERSION: u32 = 1;
+
+/// The position of a random generator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RngState {
+    seed: [u8; 32],
+    stream: u64,
+    word_pos: u128,
+}
+
+impl From<&ChaCha12Rng> for RngState {
+    fn from(rng: &ChaCha12Rng) -> RngState {
+        RngState {
+            seed: rng.get_seed(),
+            stream: rng.get_stream(),
+            word_pos: rng.get_word_pos(),
+        }
+    }
+}
+
+impl From<&RngState> for ChaCha12Rng {
+    fn from(state: &RngState) -> ChaCha12Rng {
+        let mut rng = ChaCha12Rng::from_seed(state.seed);
+        rng.set_stream(state.stream);
+        rng.set_word_pos(state.word_pos);
+        rng
+    }
+}
+
+/// A copy of a module's [`Buffer`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum BufferState {
+    Values(Vec<f64>),
+    Rng(RngState),
+}
+
+/// Everything a `Trainer` needs to carry on from the end of an epoch as if it had never
+/// stopped. The learning rate schedule follows from `epoch`.
+///
+/// Callbacks are saved through [`Callback::state_dict`](super::Callback::state_dict), so a
+/// callback that keeps state between epochs without implementing it starts afresh on resume.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TrainingCheckpoint {
+    /// Epochs completed.
+    pub epoch: usize,
+    /// Optimizer steps taken.
+    pub step: usize,
+    pub parameters: StateDict,
+    pub buffers: BTreeMap<String, BufferState>,
+    pub optimizer: OptimizerState,
+    /// The state of each callback, in the order they were added.
+    pub callbacks: Vec<Vec<f64>>,
+    /// The generator shuffling the training data, if it is shuffled.
+    pub shuffle: Option<RngState>,
+}
+
+impl TrainingCheckpoint {
+    /// Writes the checkpoint to `path` in the format described in [`resume`](self).
+    pub fn save(&self, path: impl AsRef<Path>) -> std::result::Result<(), ModelFileError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Reads a checkpoint written by [`TrainingCheckpoint::save`].
+    pub fn load(path: impl AsRef<Path>) -> std::result::Result<TrainingCheckpoint, ModelFileError> {
+        TrainingCheckpoint::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        bytes.extend((self.epoch as u64).to_le_bytes());
+        bytes.extend((self.step as u64).to_le_bytes());
+        bytes.extend((self.parameters.len() as u32).to_le_bytes());
+        for (name, value) in &self.parameters {
+            write_name(&mut bytes, name);
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend((self.buffers.len() as u32).to_le_bytes());
+        for (name, buffer) in &self.buffers {
+            write_name(&mut bytes, name);
+            match buffer {
+                BufferState::Values(values) => {
+                    bytes.push(0);
+                    write_values(&mut bytes, values);
+                }
+                BufferState::Rng(rng) => {
+                    bytes.push(1);
+                    write_rng(&mut bytes, rng);
+                }
+            }
+        }
+        bytes.extend((self.optimizer.len() as u32).to_le_bytes());
+        for (name, values) in &self.optimizer {
+            write_name(&mut bytes, name);
+            write_values(&mut bytes, values);
+        }
+        bytes.extend((self.callbacks.len() as u32).to_le_bytes());
+        for values in &self.callbacks {
+            write_values(&mut bytes, values);
+        }
+        match &self.shuffle {
+            Some(rng) => {
+                bytes.push(1);
+                write_rng(&mut bytes, rng);
+            }
+            None => bytes.push(0),
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> std::result::Result<TrainingCheckpoint, ModelFileError> {
+        let mut reader = Reader { bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(ModelFileError::BadMagic);
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(ModelFileError::UnsupportedVersion(version));
+        }
+        let mut checkpoint = TrainingCheckpoint {
+            epoch: reader.u64()? as usize,
+            step: reader.u64()? as usize,
+            ..TrainingCheckpoint::default()
+        };
+        for _ in 0..reader.u32()? {
+            let name = read_name(&mut reader)?;
+            checkpoint.parameters.insert(name, reader.f64()?);
+        }
+        for _ in 0..reader.u32()? {
+            let name = read_name(&mut reader)?;
+            let buffer = match reader.u8()? {
+                0 => BufferState::Values(read_values(&mut reader)?),
+                1 => BufferState::Rng(read_rng(&mut reader)?),
+                kind => return Err(ModelFileError::UnknownBufferKind(kind)),
+            };
+            checkpoint.buffers.insert(name, buffer);
+        }
+        for _ in 0..reader.u32()? {
+            let name = read_name(&mut reader)?;
+            checkpoint.optimizer.insert(name, read_values(&mut reader)?);
+        }
+        for _ in 0..reader.u32()? {
+            checkpoint.callbacks.push(read_values(&mut reader)?);
+        }
+        if reader.u8()? == 1 {
+            checkpoint.shuffle = Some(read_rng(&mut reader)?);
+        }
+        if !reader.bytes.is_empty() {
+            return Err(ModelFileError::TrailingBytes(reader.bytes.len()));
+        }
+        Ok(checkpoint)
+    }
+}
+
+/// A copy of every buffer, by name.
+pub(crate) fn buffer_states(named: Vec<(String, Buffer)>) -> BTreeMap<String, BufferState> {
+    named
+        .into_iter()
+        .map(|(name, buffer)| {
+            let state = match buffer {
+                Buffer::Values(values) => BufferState::Values(values.borrow().clone()),
+                Buffer::Rng(rng) => BufferState::Rng(RngState::from(&*rng.borrow())),
+            };
+            (name, state)
+        })
+        .collect()
+}
+
+/// Restores every buffer from the state of the same name, which must exist and match it.
+pub(crate) fn load_buffers(
+    named: Vec<(String, Buffer)>,
+    states: &BTreeMap<String, BufferState>,
+) -> Result<()> {
+    if named.len() != states.len() {
+        return Err(format!(
+            "checkpoint has {} buffers, the model {}",
+            states.len(),
+            named.len()
+        )
+        .into());
+    }
+    for (name, buffer) in named {
+        match (buffer, states.get(&name)) {
+            (Buffer::Values(values), Some(BufferState::Values(state)))
+                if values.borrow().len() == state.len() =>
+            {
+                values.borrow_mut().clone_from(state);
+            }
+            (Buffer::Rng(rng), Some(BufferState::Rng(state))) => {
+                *rng.borrow_mut() = ChaCha12Rng::from(state);
+            }
+            _ => return Err(format!("checkpoint has no matching buffer {name}").into()),
+        }
+    }
+    Ok(())
+}
+
+fn write_name(bytes: &mut Vec<u8>, name: &str) {
+    bytes.extend((name.len() as u32).to_le_bytes());
+    bytes.extend(name.as_bytes());
+}
+
+fn write_values(bytes: &mut Vec<u8>, values: &[f64]) {
+    bytes.extend((values.len() as u32).to_le_bytes());
+    for value in values {
+        bytes.extend(value.to_le_bytes());
+    }
+}
+
+fn write_rng(bytes: &mut Vec<u8>, rng: &RngState) {
+    bytes.extend(rng.seed);
+    bytes.extend(rng.stream.to_le_bytes());
+    bytes.extend(rng.word_pos.to_le_bytes());
+}
+
+fn read_name(reader: &mut Reader) -> std::result::Result<String, ModelFileError> {
+    let len = reader.u32()? as usize;
+    String::from_utf8(reader.take(len)?.to_vec()).map_err(|_| ModelFileError::InvalidName)
+}
+
+fn read_values(reader: &mut Reader) -> std::result::Result<Vec<f64>, ModelFileError> {
+    (0..reader.u32()?).map(|_| reader.f64()).collect()
+}
+
+fn read_rng(reader: &mut Reader) -> std::result::Result<RngState, ModelFileError> {
+    Ok(RngState {
+        seed: reader.take(32)?.try_into().expect("took 32 bytes"),
+        stream: reader.u64()?,
+        word_pos: reader.u128()?,
+    })
+}
@@ -0,0 +1,354 @@
+use std::f64::consts::PI;
+
+use super::Optimizer;
+
+/// A learning rate as a function of a step counter.
+///
+/// Whether a "step" is an optimizer step or an epoch is up to the caller, which passes the
+/// counter it keeps to `apply`.
+pub trait LrScheduler {
+    /// The learning rate at `step`, counting from zero.
+    fn lr_at(&self, step: usize) -> f64;
+
+    /// Sets the optimizer's learning rate to the one scheduled for `step`.
+    fn apply(&self, optimizer: &mut dyn Optimizer, step: usize) {
+        optimizer.set_learning_rate(self.lr_at(step));
+    }
+}
+
+impl<F: Fn(usize) -> f64> LrScheduler for F {
+    fn lr_at(&self, step: usize) -> f64 {
+        self(step)
+    }
+}
+
+/// Keeps the learning rate fixed.
+#[derive(Debug, Clone)]
+pub struct ConstantLR {
+    pub lr: f64,
+}
+
+impl LrScheduler for ConstantLR {
+    fn lr_at(&self, _step: usize) -> f64 {
+        self.lr
+    }
+}
+
+/// Moves linearly from `start` to `end` over `steps`, then stays at `end`.
+#[derive(Debug, Clone)]
+pub struct LinearLR {
+    pub start: f64,
+    pub end: f64,
+    pub steps: usize,
+}
+
+impl LinearLR {
+    pub fn new(start: f64, end: f64, steps: usize) -> LinearLR {
+        LinearLR { start, end, steps }
+    }
+}
+
+impl LrScheduler for LinearLR {
+    fn lr_at(&self, step: usize) -> f64 {
+        if step >= self.steps {
+            return self.end;
+        }
+        self.start + (self.end - self.start) * step as f64 / self.steps as f64
+    }
+}
+
+/// Multiplies the learning rate by `gamma` every `step_size` steps.
+#[derive(Debug, Clone)]
+pub struct StepLR {
+    base: f64,
+    step_size: usize,
+    gamma: f64,
+}
+
+impl StepLR {
+    pub fn new(base: f64, step_size: usize, gamma: f64) -> StepLR {
+        assert!(step_size > 0, "step size must be positive");
+        StepLR {
+            base,
+            step_size,
+            gamma,
+        }
+    }
+
+    pub fn base(&self) -> f64 {
+        self.base
+    }
+
+    pub fn step_size(&self) -> usize {
+        self.step_size
+    }
+
+    pub fn gamma(&self) -> f64 {
+        self.gamma
+    }
+}
+
+impl LrScheduler for StepLR {
+    fn lr_at(&self, step: usize) -> f64 {
+        self.base * self.gamma.powi((step / self.step_size) as i32)
+    }
+}
+
+/// Multiplies the learning rate by `gamma` every step.
+#[derive(Debug, Clone)]
+pub struct ExponentialLR {
+    pub base: f64,
+    pub gamma: f64,
+}
+
+impl ExponentialLR {
+    pub fn new(base: f64, gamma: f64) -> ExponentialLR {
+        ExponentialLR { base, gamma }
+    }
+}
+
+impl LrScheduler for ExponentialLR {
+    fn lr_at(&self, step: usize) -> f64 {
+        self.base * self.gamma.powi(step as i32)
+    }
+}
+
+/// Follows half a cosine from `base` down to `min` over `steps`, then stays at `min`.
+#[derive(Debug, Clone)]
+pub struct CosineAnnealingLR {
+    pub base: f64,
+    pub min: f64,
+    pub steps: usize,
+}
+
+impl CosineAnnealingLR {
+    pub fn new(base: f64, min: f64, steps: usize) -> CosineAnnealingLR {
+        CosineAnnealingLR { base, min, steps }
+    }
+}
+
+impl LrScheduler for CosineAnnealingLR {
+    fn lr_at(&self, step: usize) -> f64 {
+        cosine(self.base, self.min, step, self.steps)
+    }
+}
+
+/// Ramps linearly up to `schedule` over `warmup_steps`, then hands over to it.
+///
+/// During warmup the learning rate is `schedule.lr_at(0) * (step + 1) / warmup_steps`; after
+/// warmup `schedule` sees its own step counter starting again from zero.
+pub struct WarmupLR {
+    pub schedule: Box<dyn LrScheduler>,
+    pub warmup_steps: usize,
+}
+
+impl WarmupLR {
+    pub fn new(schedule: impl LrScheduler + 'static, warmup_steps: usize) -> WarmupLR {
+        WarmupLR {
+            schedule: Box::new(schedule),
+            warmup_steps,
+        }
+    }
+}
+
+impl LrScheduler for WarmupLR {
+    fn lr_at(&self, step: usize) -> f64 {
+        if step < self.warmup_steps {
+            self.schedule.lr_at(0) * (step + 1) as f64 / self.warmup_steps as f64
+        } else {
+            self.schedule.lr_at(step - self.warmup_steps)
+        }
+    }
+}
+
+/// The one-cycle policy: a cosine ramp from `max_lr / div_factor` up to `max_lr` during the
+/// first `pct_start` of `total_steps`, then a cosine decay to
+/// `max_lr / (div_factor * final_div_factor)`.
+#[derive(Debug, Clone)]
+pub struct OneCycleLR {
+    pub max_lr: f64,
+    pub total_steps: usize,
+    pub pct_start: f64,
+    pub div_factor: f64,
+    pub final_div_factor: f64,
+}
+
+impl OneCycleLR {
+    pub fn new(max_lr: f64, total_steps: usize) -> OneCycleLR {
+        OneCycleLR {
+            max_lr,
+            total_steps,
+            pct_start: 0.3,
+            div_factor: 25.0,
+            final_div_factor: 1e4,
+        }
+    }
+
+    pub fn with_pct_start(mut self, pct_start: f64) -> OneCycleLR {
+        self.pct_start = pct_start;
+        self
+    }
+
+    pub fn with_div_factors(mut self, div_factor: f64, final_div_factor: f64) -> OneCycleLR {
+        self.div_factor = div_factor;
+        self.final_div_factor = final_div_factor;
+        self
+    }
+}
+
+impl LrScheduler for OneCycleLR {
+    fn lr_at(&self, step: usize) -> f64 {
+        let initial = self.max_lr / self.div_factor;
+        let last = initial / self.final_div_factor;
+        let warmup = (self.pct_start * self.total_steps as f64).round() as usize;
+        if step < warmup {
+            cosine(initial, self.max_lr, step, warmup)
+        } else {
+            let remaining = self.total_steps.saturating_sub(warmup + 1);
+            cosine(self.max_lr, last, step - warmup, remaining)
+        }
+    }
+}
+
+/// Runs several schedules one after the other.
+///
+/// Each schedule is paired with the number of steps it runs for and sees its own step counter
+/// starting from zero; the last one keeps running past its count.
+pub struct SequentialLR {
+    schedules: Vec<(Box<dyn LrScheduler>, usize)>,
+}
+
+impl SequentialLR {
+    pub fn new(schedules: Vec<(Box<dyn LrScheduler>, usize)>) -> SequentialLR {
+        assert!(!schedules.is_empty(), "must have a schedule");
+        SequentialLR { schedules }
+    }
+
+    /// Each schedule with the number of steps it runs for.
+    pub fn schedules(&self) -> &[(Box<dyn LrScheduler>, usize)] {
+        &self.schedules
+    }
+}
+
+impl LrScheduler for SequentialLR {
+    fn lr_at(&self, step: usize) -> f64 {
+        let mut offset = 0;
+        for (i, (schedule, steps)) in self.schedules.iter().enumerate() {
+            if step < offset + steps || i == self.schedules.len() - 1 {
+                return schedule.lr_at(step - offset);
+            }
+            offset += steps;
+        }
+        unreachable!("the last schedule always matches")
+    }
+}
+
+/// Half a cosine from `from` at step 0 to `to` at `steps`, clamped afterwards.
+fn cosine(from: f64, to: f64, step: usize, steps: usize) -> f64 {
+    if steps == 0 || step >= steps {
+        return to;
+    }
+    let progress = step as f64 / steps as f64;
+    to + (from - to) * (1.0 + (PI * progress).cos()) / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micrograd::{Value, SGD};
+
+    fn lrs(schedule: &dyn LrScheduler, steps: usize) -> Vec<f64> {
+        (0..steps).map(|step| schedule.lr_at(step)).collect()
+    }
+
+    fn assert_close(got: &[f64], want: &[f64]) {
+        assert_eq!(got.len(), want.len());
+        got.iter()
+            .zip(want)
+            .for_each(|(g, w)| assert!((g - w).abs() < 1e-12, "{got:?} vs {want:?}"));
+    }
+
+    #[test]
+    fn linear_schedule() {
+        assert_close(
+            &lrs(&LinearLR::new(1.0, 0.5, 4), 6),
+            &[1.0, 0.875, 0.75, 0.625, 0.5, 0.5],
+        );
+    }
+
+    #[test]
+    fn step_schedule() {
+        let schedule = StepLR::new(1.0, 2, 0.5);
+        assert_eq!(
+            (schedule.base(), schedule.step_size(), schedule.gamma()),
+            (1.0, 2, 0.5)
+        );
+        assert_close(&lrs(&schedule, 6), &[1.0, 1.0, 0.5, 0.5, 0.25, 0.25]);
+    }
+
+    #[test]
+    #[should_panic(expected = "step size must be positive")]
+    fn step_schedule_needs_a_step_size() {
+        StepLR::new(1.0, 0, 0.5);
+    }
+
+    #[test]
+    fn exponential_schedule() {
+        assert_close(
+            &lrs(&ExponentialLR::new(2.0, 0.5), 4),
+            &[2.0, 1.0, 0.5, 0.25],
+        );
+    }
+
+    #[test]
+    fn cosine_schedule() {
+        assert_close(
+            &lrs(&CosineAnnealingLR::new(1.0, 0.0, 4), 6),
+            &[1.0, 0.8535533905932737, 0.5, 0.14644660940672627, 0.0, 0.0],
+        );
+    }
+
+    #[test]
+    fn warmup_schedule() {
+        let schedule = WarmupLR::new(StepLR::new(1.0, 2, 0.1), 4);
+        assert_close(&lrs(&schedule, 7), &[0.25, 0.5, 0.75, 1.0, 1.0, 1.0, 0.1]);
+    }
+
+    #[test]
+    fn one_cycle_schedule() {
+        let schedule = OneCycleLR::new(1.0, 11)
+            .with_pct_start(0.2)
+            .with_div_factors(10.0, 10.0);
+        let lr = lrs(&schedule, 11);
+        assert!((lr[0] - 0.1).abs() < 1e-12);
+        assert!((lr[1] - 0.55).abs() < 1e-12);
+        assert!((lr[2] - 1.0).abs() < 1e-12);
+        assert!((lr[10] - 0.01).abs() < 1e-12);
+        assert!(lr[2..].windows(2).all(|w| w[1] < w[0]));
+    }
+
+    #[test]
+    fn sequential_schedule() {
+        let schedule = SequentialLR::new(vec![
+            (Box::new(LinearLR::new(0.0, 1.0, 2)), 2),
+            (Box::new(ExponentialLR::new(1.0, 0.5)), 2),
+            (Box::new(ConstantLR { lr: 0.1 }), 0),
+        ]);
+        assert_close(&lrs(&schedule, 6), &[0.0, 0.5, 1.0, 0.5, 0.1, 0.1]);
+        assert_eq!(schedule.schedules().len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "must have a schedule")]
+    fn sequential_schedule_needs_a_schedule() {
+        SequentialLR::new(Vec::new());
+    }
+
+    #[test]
+    fn closure_schedule_drives_optimizer() {
+        let mut optimizer = SGD::new(vec![Value::from(1.0)], 1.0);
+        let schedule = |step: usize| 1.0 / (step + 1) as f64;
+        schedule.apply(&mut optimizer, 3);
+        assert_eq!(optimizer.learning_rate(), 0.25);
+    }
+}
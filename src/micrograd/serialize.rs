@@ -0,0 +1,320 @@
+//! Saving and loading `MLP`s.
+//!
+//! The file format is little-endian binary:
+//!
+//! ```text
+//! magic         4 bytes  b"MGRD"
+//! version       u32      2
+//! nin           u32      inputs to the first layer
+//! layers        u32      number of layers, each followed by
+//!   nout        u32      neurons in the layer
+//!   activation  u8       0 identity, 1 relu, 2 tanh, 3 sigmoid, 4 leaky relu
+//!   parameter   f64      slope of the leaky relu, 0 otherwise
+//!   dropout     f64      probability in [0, 1) the layer's outputs are dropped, 0 for none
+//!   nout times:
+//!     bias      f64
+//!     weights   nin x f64, where nin is the previous layer's nout
+//! ```
+//!
+//! Floats are stored bit for bit, so a loaded model computes exactly what the saved one did.
+//! Version 1 files, which predate dropout, are still read. Dropout masks are not saved, so a
+//! loaded model draws new ones, and it starts in evaluation mode.
+
+use std::{fmt, fs, io, path::Path};
+
+use super::{Activation, Dropout, Layer, Module, Neuron, MLP};
+
+const MAGIC: &[u8; 4] = b"MGRD";
+const VERSION: u32 = 2;
+
+#[derive(Debug)]
+pub enum ModelFileError {
+    Io(io::Error),
+    /// The file does not start with the `MGRD` magic bytes.
+    BadMagic,
+    UnsupportedVersion(u32),
+    UnknownActivation(u8),
+    /// The file ended in the middle of the model.
+    Truncated,
+    /// The model ended but this many bytes were left over.
+    TrailingBytes(usize),
+    /// A parameter or state name is not valid UTF-8.
+    InvalidName,
+    UnknownBufferKind(u8),
+    /// A layer's dropout probability is outside `[0, 1)`.
+    InvalidDropout(f64),
+}
+
+// region:    --- Error Boilerplate
+
+impl fmt::Display for ModelFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelFileError::Io(err) => write!(f, "model file i/o failed: {err}"),
+            ModelFileError::BadMagic => write!(f, "not a model file"),
+            ModelFileError::UnsupportedVersion(version) => {
+                write!(f, "unsupported model file version {version}")
+            }
+            ModelFileError::UnknownActivation(tag) => write!(f, "unknown activation tag {tag}"),
+            ModelFileError::Truncated => write!(f, "model file is truncated"),
+            ModelFileError::TrailingBytes(n) => {
+                write!(f, "model file has {n} unexpected trailing bytes")
+            }
+            ModelFileError::InvalidName => write!(f, "name in model file is not UTF-8"),
+            ModelFileError::UnknownBufferKind(kind) => write!(f, "unknown buffer kind {kind}"),
+            ModelFileError::InvalidDropout(p) => write!(f, "invalid dropout probability {p}"),
+        }
+    }
+}
+
+impl std::error::Error for ModelFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ModelFileError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ModelFileError {
+    fn from(err: io::Error) -> ModelFileError {
+        ModelFileError::Io(err)
+    }
+}
+
+// endregion: --- Error Boilerplate
+
+impl MLP {
+    /// Writes the architecture and all parameters to `path` in the format described in
+    /// [`serialize`](self).
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ModelFileError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    /// Reads a model written by [`MLP::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<MLP, ModelFileError> {
+        MLP::from_bytes(&fs::read(path)?)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        let nin = self.layers.first().map_or(0, |layer| layer.nin);
+        bytes.extend((nin as u32).to_le_bytes());
+        bytes.extend((self.layers.len() as u32).to_le_bytes());
+        for layer in &self.layers {
+            bytes.extend((layer.neurons.len() as u32).to_le_bytes());
+            let (tag, parameter) = match layer.activation {
+                Activation::Identity => (0, 0.0),
+                Activation::Relu => (1, 0.0),
+                Activation::Tanh => (2, 0.0),
+                Activation::Sigmoid => (3, 0.0),
+                Activation::LeakyRelu(slope) => (4, slope),
+            };
+            bytes.push(tag);
+            bytes.extend(f64::to_le_bytes(parameter));
+            bytes.extend(layer.dropout().map_or(0.0, Dropout::p).to_le_bytes());
+            for p in layer.parameters() {
+                bytes.extend(p.borrow().data.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<MLP, ModelFileError> {
+        let mut reader = Reader { bytes };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(ModelFileError::BadMagic);
+        }
+        let version = reader.u32()?;
+        if !(1..=VERSION).contains(&version) {
+            return Err(ModelFileError::UnsupportedVersion(version));
+        }
+        let mut nin = reader.u32()? as usize;
+        let n_layers = reader.u32()?;
+        let mut layers = Vec::new();
+        for _ in 0..n_layers {
+            let nout = reader.u32()? as usize;
+            let tag = reader.u8()?;
+            let parameter = reader.f64()?;
+            let dropout = if version >= 2 { reader.f64()? } else { 0.0 };
+            if !(0.0..1.0).contains(&dropout) {
+                return Err(ModelFileError::InvalidDropout(dropout));
+            }
+            let activation = match tag {
+                0 => Activation::Identity,
+                1 => Activation::Relu,
+                2 => Activation::Tanh,
+                3 => Activation::Sigmoid,
+                4 => Activation::LeakyRelu(parameter),
+                _ => return Err(ModelFileError::UnknownActivation(tag)),
+            };
+            let mut neurons = Vec::new();
+            for _ in 0..nout {
+                let bias = reader.f64()?;
+                let weights = (0..nin).map(|_| reader.f64()).collect::<Result<_, _>>()?;
+                neurons.push(Neuron::from((weights, bias, activation)));
+            }
+            layers.push(Layer {
+                neurons,
+                nin,
+                activation,
+                dropout: (dropout > 0.0).then(|| Dropout::new(dropout)),
+                training: false,
+            });
+            nin = nout;
+        }
+        if !reader.bytes.is_empty() {
+            return Err(ModelFileError::TrailingBytes(reader.bytes.len()));
+        }
+        Ok(MLP {
+            layers,
+            training: false,
+        })
+    }
+}
+
+pub(crate) struct Reader<'a> {
+    pub(crate) bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8], ModelFileError> {
+        if self.bytes.len() < n {
+            return Err(ModelFileError::Truncated);
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, ModelFileError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, ModelFileError> {
+        let bytes = self.take(4)?.try_into().expect("took 4 bytes");
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, ModelFileError> {
+        let bytes = self.take(8)?.try_into().expect("took 8 bytes");
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn u128(&mut self) -> Result<u128, ModelFileError> {
+        let bytes = self.take(16)?.try_into().expect("took 16 bytes");
+        Ok(u128::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn f64(&mut self) -> Result<f64, ModelFileError> {
+        let bytes = self.take(8)?.try_into().expect("took 8 bytes");
+        Ok(f64::from_le_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micrograd::{Init, Value};
+    use std::env;
+    use uuid::Uuid;
+
+    fn model() -> MLP {
+        MLP::builder(3)
+            .with_init(Init::XavierNormal)
+            .layer(5, Activation::Tanh)
+            .dropout(0.2)
+            .layer(4, Activation::LeakyRelu(0.02))
+            .layer(2, Activation::Sigmoid)
+            .with_seed(11)
+            .build()
+    }
+
+    fn outputs(model: &MLP) -> Vec<f64> {
+        let input = vec![Value::from(0.3), Value::from(-1.1), Value::from(2.0)];
+        model.forward(&input).iter().map(Value::data).collect()
+    }
+
+    #[test]
+    fn save_and_load_round_trip() -> crate::Result<()> {
+        let original = model();
+        let path = env::temp_dir().join(format!("neural_net_model_{}.bin", Uuid::new_v4()));
+        original.save(&path)?;
+        let loaded = MLP::load(&path)?;
+        std::fs::remove_file(&path)?;
+
+        assert_eq!(outputs(&loaded), outputs(&original));
+        let activations = |m: &MLP| m.layers.iter().map(Layer::activation).collect::<Vec<_>>();
+        assert_eq!(activations(&loaded), activations(&original));
+        assert_eq!(loaded.layers[0].dropout().map(Dropout::p), Some(0.2));
+        assert!(loaded.layers[1].dropout().is_none());
+        assert_eq!(loaded.to_bytes(), original.to_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn empty_layers_keep_their_activation() -> crate::Result<()> {
+        let model = MLP::builder(2)
+            .layer(0, Activation::LeakyRelu(0.1))
+            .layer(1, Activation::Sigmoid)
+            .build();
+        let loaded = MLP::from_bytes(&model.to_bytes())?;
+        assert_eq!(loaded.layers[0].activation(), Activation::LeakyRelu(0.1));
+        assert_eq!(loaded.to_bytes(), model.to_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn version_one_files_load_without_dropout() -> crate::Result<()> {
+        let mut bytes = MAGIC.to_vec();
+        for n in [1u32, 2, 1, 1] {
+            bytes.extend(n.to_le_bytes());
+        }
+        bytes.push(0);
+        for x in [0.0, 0.5, 3.0, -1.0] {
+            bytes.extend(f64::to_le_bytes(x));
+        }
+
+        let model = MLP::from_bytes(&bytes)?;
+        assert!(model.layers[0].dropout().is_none());
+        let output = model.forward(&[Value::from(2.0), Value::from(1.0)]);
+        assert_eq!(output[0].data(), 5.5);
+        Ok(())
+    }
+
+    #[test]
+    fn mismatched_files_are_rejected() {
+        let bytes = model().to_bytes();
+        let load = |bytes: &[u8]| MLP::from_bytes(bytes).unwrap_err();
+
+        assert!(matches!(load(b"JUNKJUNK"), ModelFileError::BadMagic));
+        let mut future = bytes.clone();
+        future[4] = 9;
+        assert!(matches!(
+            load(&future),
+            ModelFileError::UnsupportedVersion(9)
+        ));
+        let mut activation = bytes.clone();
+        activation[20] = 42;
+        assert!(matches!(
+            load(&activation),
+            ModelFileError::UnknownActivation(42)
+        ));
+        for p in [1.5, -0.1, f64::NAN] {
+            let mut dropout = bytes.clone();
+            dropout[29..37].copy_from_slice(&p.to_le_bytes());
+            assert!(matches!(load(&dropout), ModelFileError::InvalidDropout(_)));
+        }
+        assert!(matches!(
+            load(&bytes[..bytes.len() - 1]),
+            ModelFileError::Truncated
+        ));
+        let trailing = [bytes.as_slice(), &[0, 0]].concat();
+        assert!(matches!(load(&trailing), ModelFileError::TrailingBytes(2)));
+
+        let missing = MLP::load(env::temp_dir().join("neural_net_no_such_model.bin"));
+        assert!(matches!(missing, Err(ModelFileError::Io(_))));
+    }
+}
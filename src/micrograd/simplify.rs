@@ -0,0 +1,326 @@
+use std::collections::{HashMap, HashSet};
+
+use uuid::Uuid;
+
+use super::{Operation, Value};
+
+/// Rewrites the graphs below `roots` into smaller, equivalent graphs.
+///
+/// Every node in `wrt` is kept as is, so gradients still land in the original `Value`s; every
+/// other leaf is treated as a constant. Constants are folded, identities such as `x + 0`,
+/// `x * 1`, `-(-x)` and `x.pow(1)` are removed and repeated subexpressions are shared.
+pub fn simplify(roots: &[Value], wrt: &[Value]) -> Vec<Value> {
+    let mut simplifier = Simplifier {
+        wrt: wrt.iter().cloned().collect(),
+        ..Default::default()
+    };
+    roots
+        .iter()
+        .map(|root| {
+            let node = simplifier.visit(root);
+            simplifier.value(&node)
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+enum Node {
+    Const(f64),
+    Expr(Value),
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum Key {
+    Const(u64),
+    Expr(Uuid),
+}
+
+impl Node {
+    fn key(&self) -> Key {
+        match self {
+            Node::Const(c) => Key::Const(c.to_bits()),
+            Node::Expr(v) => Key::Expr(v.borrow().uuid),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Simplifier {
+    wrt: HashSet<Value>,
+    done: HashMap<Value, Node>,
+    constants: HashMap<u64, Value>,
+    constant_leaves: HashMap<Value, f64>,
+    interned: HashMap<(Operation, Vec<Key>), Value>,
+}
+
+impl Simplifier {
+    fn visit(&mut self, v: &Value) -> Node {
+        if let Some(node) = self.done.get(v) {
+            return node.clone();
+        }
+        let node = if self.wrt.contains(v) {
+            Node::Expr(v.clone())
+        } else {
+            let op = v.borrow()._op;
+            let prev = v.borrow()._prev.clone();
+            match op {
+                None => Node::Const(v.borrow().data),
+                Some(Operation::Checkpoint | Operation::CheckpointOutput(_)) => {
+                    Node::Expr(v.clone())
+                }
+                Some(Operation::Add) => {
+                    let (a, b) = (self.visit(&prev[0]), self.visit(&prev[1]));
+                    self.add(a, b)
+                }
+                Some(Operation::Sub) => {
+                    let (a, b) = (self.visit(&prev[0]), self.visit(&prev[1]));
+                    let neg_b = self.mul(Node::Const(-1.0), b);
+                    self.add(a, neg_b)
+                }
+                Some(Operation::Mul) => {
+                    let (a, b) = (self.visit(&prev[0]), self.visit(&prev[1]));
+                    self.mul(a, b)
+                }
+                Some(Operation::Div) => {
+                    let (a, b) = (self.visit(&prev[0]), self.visit(&prev[1]));
+                    let inv_b = self.pow(b, -1.0);
+                    self.mul(a, inv_b)
+                }
+                Some(Operation::Pow) => {
+                    let a = self.visit(&prev[0]);
+                    let power = prev[1].borrow().data;
+                    self.pow(a, power)
+                }
+                Some(Operation::Relu) => match self.visit(&prev[0]) {
+                    Node::Const(c) => Node::Const(c.max(0.0)),
+                    a => self.intern(Operation::Relu, vec![a]),
+                },
+                Some(Operation::Max) => match (self.visit(&prev[0]), self.visit(&prev[1])) {
+                    (Node::Const(x), Node::Const(y)) => Node::Const(x.max(y)),
+                    (a, b) => self.intern(Operation::Max, vec![a, b]),
+                },
+                Some(Operation::Exp) => match self.visit(&prev[0]) {
+                    Node::Const(c) => Node::Const(c.exp()),
+                    a => self.intern(Operation::Exp, vec![a]),
+                },
+                Some(Operation::Log) => match self.visit(&prev[0]) {
+                    Node::Const(c) => Node::Const(c.ln()),
+                    a => self.intern(Operation::Log, vec![a]),
+                },
+                Some(Operation::Abs) => match self.visit(&prev[0]) {
+                    Node::Const(c) => Node::Const(c.abs()),
+                    a => self.intern(Operation::Abs, vec![a]),
+                },
+                Some(Operation::Tanh) => match self.visit(&prev[0]) {
+                    Node::Const(c) => Node::Const(c.tanh()),
+                    a => self.intern(Operation::Tanh, vec![a]),
+                },
+                Some(Operation::Sigmoid) => match self.visit(&prev[0]) {
+                    Node::Const(c) => Node::Const(1.0 / (1.0 + (-c).exp())),
+                    a => self.intern(Operation::Sigmoid, vec![a]),
+                },
+                Some(Operation::LeakyRelu) => {
+                    let slope = prev[1].borrow().data;
+                    match self.visit(&prev[0]) {
+                        Node::Const(c) => Node::Const(if c > 0.0 { c } else { slope * c }),
+                        a => self.intern(Operation::LeakyRelu, vec![a, Node::Const(slope)]),
+                    }
+                }
+            }
+        };
+        self.done.insert(v.clone(), node.clone());
+        node
+    }
+
+    fn add(&mut self, a: Node, b: Node) -> Node {
+        match (a, b) {
+            (Node::Const(x), Node::Const(y)) => Node::Const(x + y),
+            (Node::Const(c), e) | (e, Node::Const(c)) if c == 0.0 => e,
+            (Node::Const(c), Node::Expr(e)) | (Node::Expr(e), Node::Const(c)) => {
+                match self.split_constant(&e, Operation::Add) {
+                    Some((k, rest)) => self.add(Node::Const(c + k), rest),
+                    None => self.intern(Operation::Add, vec![Node::Expr(e), Node::Const(c)]),
+                }
+            }
+            (a, b) => self.intern(Operation::Add, vec![a, b]),
+        }
+    }
+
+    fn mul(&mut self, a: Node, b: Node) -> Node {
+        match (a, b) {
+            (Node::Const(x), Node::Const(y)) => Node::Const(x * y),
+            (Node::Const(c), _) | (_, Node::Const(c)) if c == 0.0 => Node::Const(0.0),
+            (Node::Const(c), e) | (e, Node::Const(c)) if c == 1.0 => e,
+            (Node::Const(c), Node::Expr(e)) | (Node::Expr(e), Node::Const(c)) => {
+                match self.split_constant(&e, Operation::Mul) {
+                    Some((k, rest)) => self.mul(Node::Const(c * k), rest),
+                    None => self.intern(Operation::Mul, vec![Node::Expr(e), Node::Const(c)]),
+                }
+            }
+            (a, b) => self.intern(Operation::Mul, vec![a, b]),
+        }
+    }
+
+    fn pow(&mut self, a: Node, power: f64) -> Node {
+        match a {
+            Node::Const(c) => Node::Const(c.powf(power)),
+            a if power == 1.0 => a,
+            _ if power == 0.0 => Node::Const(1.0),
+            a => self.intern(Operation::Pow, vec![a, Node::Const(power)]),
+        }
+    }
+
+    /// Splits `k op rest` into its folded constant `k` and the remaining operand.
+    fn split_constant(&self, e: &Value, op: Operation) -> Option<(f64, Node)> {
+        if e.borrow()._op != Some(op) {
+            return None;
+        }
+        let data = e.borrow();
+        let prev = &data._prev;
+        [(0, 1), (1, 0)].into_iter().find_map(|(c, rest)| {
+            self.constant_leaves
+                .get(&prev[c])
+                .map(|k| (*k, self.node_of(&prev[rest])))
+        })
+    }
+
+    fn node_of(&self, v: &Value) -> Node {
+        match self.constant_leaves.get(v) {
+            Some(c) => Node::Const(*c),
+            None => Node::Expr(v.clone()),
+        }
+    }
+
+    fn intern(&mut self, op: Operation, operands: Vec<Node>) -> Node {
+        let mut key: Vec<Key> = operands.iter().map(Node::key).collect();
+        if matches!(op, Operation::Add | Operation::Mul) {
+            key.sort();
+        }
+        if let Some(existing) = self.interned.get(&(op, key.clone())) {
+            return Node::Expr(existing.clone());
+        }
+        let values: Vec<Value> = operands.iter().map(|n| self.value(n)).collect();
+        let result = match op {
+            Operation::Add => &values[0] + &values[1],
+            Operation::Mul => &values[0] * &values[1],
+            Operation::Pow => values[0].pow(values[1].borrow().data),
+            Operation::Relu => values[0].relu(),
+            Operation::Max => values[0].max(&values[1]),
+            Operation::Exp => values[0].exp(),
+            Operation::Log => values[0].ln(),
+            Operation::Abs => values[0].abs(),
+            Operation::Tanh => values[0].tanh(),
+            Operation::Sigmoid => values[0].sigmoid(),
+            Operation::LeakyRelu => values[0].leaky_relu(values[1].borrow().data),
+            Operation::Sub
+            | Operation::Div
+            | Operation::Checkpoint
+            | Operation::CheckpointOutput(_) => {
+                unreachable!("{op:?} is rewritten before interning")
+            }
+        };
+        self.interned.insert((op, key), result.clone());
+        Node::Expr(result)
+    }
+
+    fn value(&mut self, node: &Node) -> Value {
+        match node {
+            Node::Expr(v) => v.clone(),
+            Node::Const(c) => {
+                let constant = self
+                    .constants
+                    .entry(c.to_bits())
+                    .or_insert_with(|| Value::from(*c))
+                    .clone();
+                self.constant_leaves.insert(constant.clone(), *c);
+                constant
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micrograd::{draw_dots, Module, MLP};
+
+    #[allow(clippy::mutable_key_type)]
+    fn count_nodes(roots: &[Value]) -> usize {
+        fn walk(v: &Value, seen: &mut HashSet<Value>) {
+            if seen.insert(v.clone()) {
+                v.borrow()._prev.iter().for_each(|child| walk(child, seen));
+            }
+        }
+        let mut seen = HashSet::new();
+        roots.iter().for_each(|root| walk(root, &mut seen));
+        seen.len()
+    }
+
+    fn grads(values: &[Value]) -> Vec<f64> {
+        values.iter().map(|v| v.borrow().grad).collect()
+    }
+
+    #[test]
+    fn simplify_removes_identities() {
+        let x = Value::from(3.0);
+        let w = Value::from(-2.0);
+        let bias = Value::from(0.0);
+        let expr = &(-&(-&(&x * &w))).pow(1.0) + &bias;
+        let simplified = simplify(std::slice::from_ref(&expr), &[x.clone(), w.clone()]);
+
+        assert_eq!(count_nodes(&simplified), 3);
+        assert_eq!(simplified[0].borrow().data, expr.borrow().data);
+        simplified[0].backward();
+        assert_eq!(x.borrow().grad, -2.0);
+        assert_eq!(w.borrow().grad, 3.0);
+    }
+
+    #[test]
+    fn simplify_folds_constants() {
+        let x = Value::from(2.0);
+        let expr = &(&x * &(&Value::from(2.0) + &Value::from(1.0))) / &Value::from(4.0);
+        let simplified = simplify(std::slice::from_ref(&expr), std::slice::from_ref(&x));
+
+        assert_eq!(count_nodes(&simplified), 3);
+        assert_eq!(simplified[0].borrow().data, 1.5);
+        simplified[0].backward();
+        assert_eq!(x.borrow().grad, 0.75);
+    }
+
+    #[test]
+    fn simplify_merges_common_subexpressions() {
+        let x = Value::from(2.0);
+        let y = Value::from(5.0);
+        let expr = &(&x * &y) + &(&y * &x);
+        let simplified = simplify(std::slice::from_ref(&expr), &[x.clone(), y.clone()]);
+
+        assert_eq!(count_nodes(&simplified), 4);
+        simplified[0].backward();
+        assert_eq!(x.borrow().grad, 10.0);
+        assert_eq!(y.borrow().grad, 4.0);
+    }
+
+    #[test]
+    fn simplify_mlp_graph_matches() -> crate::Result<()> {
+        let model = MLP::new(vec![2, 4, 4, 1]);
+        let params = model.parameters();
+        let inputs = vec![Value::from(0.5), Value::from(-1.5)];
+        let output = model.forward(&inputs);
+        let loss = &(&output[0] - &Value::from(1.0)).pow(2.0) + &Value::from(0.0);
+        loss.backward();
+        let expected = grads(&params);
+        model.zero_grad();
+
+        let simplified = simplify(std::slice::from_ref(&loss), &params);
+        assert!(count_nodes(&simplified) < count_nodes(std::slice::from_ref(&loss)));
+        assert!((simplified[0].borrow().data - loss.borrow().data).abs() < 1e-12);
+        simplified[0].backward();
+        grads(&params)
+            .iter()
+            .zip(&expected)
+            .for_each(|(got, want)| assert!((got - want).abs() < 1e-12));
+
+        assert!(draw_dots(simplified[0].clone()).is_ok());
+        Ok(())
+    }
+}
@@ -0,0 +1,802 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+use super::{
+    resume::{buffer_states, load_buffers},
+    DataLoader, Dataset, InMemoryDataset, LrScheduler, Module, Optimizer, RngState,
+    TrainingCheckpoint, Value, MLP,
+};
+
+use crate::Result;
+
+/// Metric values of one epoch by name, e.g. `loss`, `val_loss` or `lr`.
+pub type Metrics = BTreeMap<String, f64>;
+
+type LossFn<'a> = Box<dyn Fn(&[Vec<Value>], &[Vec<f64>]) -> Value + 'a>;
+type MetricFn<'a> = Box<dyn Fn(&[Vec<f64>], &[Vec<f64>]) -> f64 + 'a>;
+
+/// Whether training goes on after a callback has seen an epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Stop,
+}
+
+/// Whether a monitored metric improves by going down or up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Min,
+    Max,
+}
+
+/// Hook run by a `Trainer` after every epoch, given the model being trained.
+pub trait Callback<M: ?Sized = MLP> {
+    fn on_epoch_end(&mut self, epoch: usize, metrics: &Metrics, model: &M) -> Result<Control>;
+
+    /// State carried over epochs, saved in a [`TrainingCheckpoint`]. Empty for callbacks that
+    /// keep none.
+    fn state_dict(&self) -> Vec<f64> {
+        Vec::new()
+    }
+
+    /// Restores the state returned by [`Callback::state_dict`].
+    fn load_state_dict(&mut self, state: &[f64]) -> Result<()> {
+        if state.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("callback keeps no state, got {} values", state.len()).into())
+        }
+    }
+}
+
+/// Per-epoch metrics recorded by `Trainer::fit`.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    pub epochs: Vec<Metrics>,
+}
+
+impl History {
+    /// The values of one metric over all epochs it was recorded in.
+    pub fn metric(&self, name: &str) -> Vec<f64> {
+        self.epochs
+            .iter()
+            .filter_map(|metrics| metrics.get(name).copied())
+            .collect()
+    }
+
+    pub fn last(&self) -> Option<&Metrics> {
+        self.epochs.last()
+    }
+}
+
+/// Runs the forward, backward and update loop over a dataset for a number of epochs.
+///
+/// The loss is given the model outputs and targets of a batch. Every epoch records the mean
+/// training `loss`, each metric under its own name, the learning rate as `lr` and, with
+/// validation data, `val_loss` and `val_<metric>`. Validation runs with the model in
+/// evaluation mode, which is restored to its previous mode afterwards.
+pub struct Trainer<'a, M: ?Sized = MLP> {
+    model: &'a mut M,
+    optimizer: Box<dyn Optimizer + 'a>,
+    loss: LossFn<'a>,
+    metrics: Vec<(String, MetricFn<'a>)>,
+    callbacks: Vec<Box<dyn Callback<M> + 'a>>,
+    scheduler: Option<Box<dyn LrScheduler + 'a>>,
+    batch_size: Option<usize>,
+    shuffle: Option<ChaCha12Rng>,
+    validation: Option<&'a dyn Dataset>,
+    checkpoints: Option<(PathBuf, usize)>,
+    epoch: usize,
+    step: usize,
+}
+
+impl<'a, M: Module + ?Sized> Trainer<'a, M> {
+    pub fn new(
+        model: &'a mut M,
+        optimizer: impl Optimizer + 'a,
+        loss: impl Fn(&[Vec<Value>], &[Vec<f64>]) -> Value + 'a,
+    ) -> Trainer<'a, M> {
+        Trainer {
+            model,
+            optimizer: Box::new(optimizer),
+            loss: Box::new(loss),
+            metrics: Vec::new(),
+            callbacks: Vec::new(),
+            scheduler: None,
+            batch_size: None,
+            shuffle: None,
+            validation: None,
+            checkpoints: None,
+            epoch: 0,
+            step: 0,
+        }
+    }
+
+    /// Records `metric(outputs, targets)` every epoch, on validation data too if there is any.
+    pub fn with_metric(
+        mut self,
+        name: &str,
+        metric: impl Fn(&[Vec<f64>], &[Vec<f64>]) -> f64 + 'a,
+    ) -> Trainer<'a, M> {
+        self.metrics.push((name.to_string(), Box::new(metric)));
+        self
+    }
+
+    pub fn with_callback(mut self, callback: impl Callback<M> + 'a) -> Trainer<'a, M> {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// Sets the learning rate from `scheduler` at the start of every epoch.
+    pub fn with_scheduler(mut self, scheduler: impl LrScheduler + 'a) -> Trainer<'a, M> {
+        self.scheduler = Some(Box::new(scheduler));
+        self
+    }
+
+    /// Updates after every `batch_size` samples instead of once per epoch.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Trainer<'a, M> {
+        assert!(batch_size > 0, "batch size must be positive");
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Visits the samples in a different order every epoch, shuffled with a seeded generator.
+    pub fn with_shuffle(mut self, seed: u64) -> Trainer<'a, M> {
+        self.shuffle = Some(ChaCha12Rng::seed_from_u64(seed));
+        self
+    }
+
+    pub fn with_validation(mut self, dataset: &'a dyn Dataset) -> Trainer<'a, M> {
+        self.validation = Some(dataset);
+        self
+    }
+
+    /// Saves a [`TrainingCheckpoint`] to `path` after every `every` epochs, replacing the
+    /// previous one.
+    pub fn with_checkpoints(mut self, path: impl Into<PathBuf>, every: usize) -> Trainer<'a, M> {
+        assert!(every > 0, "checkpoint interval must be positive");
+        self.checkpoints = Some((path.into(), every));
+        self
+    }
+
+    pub fn optimizer(&self) -> &dyn Optimizer {
+        self.optimizer.as_ref()
+    }
+
+    /// Epochs completed so far.
+    pub fn epoch(&self) -> usize {
+        self.epoch
+    }
+
+    /// Optimizer steps taken so far.
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// The state of training at the end of the last epoch.
+    pub fn checkpoint(&self) -> TrainingCheckpoint {
+        TrainingCheckpoint {
+            epoch: self.epoch,
+            step: self.step,
+            parameters: self.model.state_dict(),
+            buffers: buffer_states(self.model.named_buffers()),
+            optimizer: self.optimizer.state_dict(),
+            callbacks: self.callbacks.iter().map(|c| c.state_dict()).collect(),
+            shuffle: self.shuffle.as_ref().map(RngState::from),
+        }
+    }
+
+    /// Restores the model, optimizer, callbacks, counters and random generators from
+    /// `checkpoint`, so [`fit_until`](Trainer::fit_until) carries on exactly where the
+    /// checkpointed run stopped. The trainer must be set up like the one that saved it, with the
+    /// same callbacks in the same order.
+    pub fn resume(&mut self, checkpoint: &TrainingCheckpoint) -> Result<()> {
+        if self.shuffle.is_some() != checkpoint.shuffle.is_some() {
+            return Err("checkpoint and trainer disagree on shuffling".into());
+        }
+        if self.callbacks.len() != checkpoint.callbacks.len() {
+            return Err(format!(
+                "checkpoint has {} callbacks, the trainer {}",
+                checkpoint.callbacks.len(),
+                self.callbacks.len()
+            )
+            .into());
+        }
+        let report = self.model.load_state_dict(&checkpoint.parameters);
+        if !report.is_exact() {
+            return Err(format!("checkpoint does not match the model, {report}").into());
+        }
+        load_buffers(self.model.named_buffers(), &checkpoint.buffers)?;
+        self.optimizer.load_state_dict(&checkpoint.optimizer)?;
+        for (callback, state) in self.callbacks.iter_mut().zip(&checkpoint.callbacks) {
+            callback.load_state_dict(state)?;
+        }
+        self.shuffle = checkpoint.shuffle.as_ref().map(ChaCha12Rng::from);
+        self.epoch = checkpoint.epoch;
+        self.step = checkpoint.step;
+        Ok(())
+    }
+
+    /// Trains on `dataset` for up to `epochs` epochs, fewer if a callback stops it. Epochs are
+    /// numbered, and the learning rate scheduled, on from those of earlier calls.
+    pub fn fit(&mut self, dataset: &dyn Dataset, epochs: usize) -> Result<History> {
+        self.fit_until(dataset, self.epoch + epochs)
+    }
+
+    /// Trains on `dataset` until `epochs` epochs have run in total, counting those before a
+    /// [`resume`](Trainer::resume), fewer if a callback stops it. The history holds only the
+    /// epochs of this call.
+    pub fn fit_until(&mut self, dataset: &dyn Dataset, epochs: usize) -> Result<History> {
+        if dataset.is_empty() {
+            return Err("must have training data".into());
+        }
+        let mut loader = DataLoader::new(dataset, self.batch_size.unwrap_or(dataset.len()));
+        loader.rng = self.shuffle.clone();
+        let validation = self.validation.map(|dataset| {
+            InMemoryDataset::from_indices(dataset, &(0..dataset.len()).collect::<Vec<_>>())
+        });
+        let mut history = History::default();
+        for epoch in self.epoch..epochs {
+            if let Some(scheduler) = &self.scheduler {
+                scheduler.apply(self.optimizer.as_mut(), epoch);
+            }
+            let mut metrics = Metrics::new();
+            metrics.insert("lr".to_string(), self.optimizer.learning_rate());
+
+            let mut total_loss = 0.0;
+            let mut outputs = Vec::with_capacity(dataset.len());
+            let mut targets = Vec::with_capacity(dataset.len());
+            for batch in loader.epoch() {
+                let predicted = self.forward(&batch.inputs);
+                let loss = (self.loss)(&predicted, &batch.targets);
+                self.optimizer.zero_grad();
+                loss.backward();
+                self.optimizer.step();
+                self.step += 1;
+                total_loss += loss.data() * batch.inputs.len() as f64;
+                outputs.extend(predicted.iter().map(|row| data(row)));
+                targets.extend(batch.targets);
+            }
+            metrics.insert("loss".to_string(), total_loss / dataset.len() as f64);
+            self.record(&mut metrics, "", &outputs, &targets);
+
+            if let Some(validation) = &validation {
+                let training = self.model.is_training();
+                self.model.eval();
+                let predicted = self.forward(validation.inputs());
+                self.model.set_training(training);
+                let val_loss = (self.loss)(&predicted, validation.targets()).data();
+                let outputs: Vec<Vec<f64>> = predicted.iter().map(|row| data(row)).collect();
+                metrics.insert("val_loss".to_string(), val_loss);
+                self.record(&mut metrics, "val_", &outputs, validation.targets());
+            }
+
+            let mut stop = false;
+            for callback in &mut self.callbacks {
+                stop |= callback.on_epoch_end(epoch, &metrics, &*self.model)? == Control::Stop;
+            }
+
+            // saved once the callbacks have seen the epoch, so their state is up to date
+            self.epoch = epoch + 1;
+            self.shuffle.clone_from(&loader.rng);
+            if let Some((path, every)) = &self.checkpoints {
+                if self.epoch.is_multiple_of(*every) {
+                    self.checkpoint().save(path)?;
+                }
+            }
+            history.epochs.push(metrics);
+            if stop {
+                break;
+            }
+        }
+        Ok(history)
+    }
+
+    fn forward(&self, inputs: &[Vec<f64>]) -> Vec<Vec<Value>> {
+        let inputs: Vec<Vec<Value>> = inputs
+            .iter()
+            .map(|x| x.iter().map(|&v| Value::from(v)).collect())
+            .collect();
+        self.model.forward_batch(&inputs)
+    }
+
+    fn record(
+        &self,
+        metrics: &mut Metrics,
+        prefix: &str,
+        outputs: &[Vec<f64>],
+        targets: &[Vec<f64>],
+    ) {
+        for (name, metric) in &self.metrics {
+            metrics.insert(format!("{prefix}{name}"), metric(outputs, targets));
+        }
+    }
+}
+
+fn data(row: &[Value]) -> Vec<f64> {
+    row.iter().map(Value::data).collect()
+}
+
+fn monitored(metrics: &Metrics, name: &str) -> Result<f64> {
+    metrics
+        .get(name)
+        .copied()
+        .ok_or_else(|| format!("metric {name} was not recorded").into())
+}
+
+/// Tracks the best value seen of a monitored metric.
+#[derive(Debug, Clone)]
+struct Best {
+    mode: Mode,
+    min_delta: f64,
+    value: Option<f64>,
+}
+
+impl Best {
+    /// Whether there is a best value yet, and the value.
+    fn state(&self) -> [f64; 2] {
+        match self.value {
+            Some(value) => [1.0, value],
+            None => [0.0, 0.0],
+        }
+    }
+
+    fn load_state(&mut self, state: &[f64]) {
+        self.value = (state[0] == 1.0).then_some(state[1]);
+    }
+
+    fn improved(&mut self, value: f64) -> bool {
+        let better = match (self.value, self.mode) {
+            (None, _) => true,
+            (Some(best), Mode::Min) => value < best - self.min_delta,
+            (Some(best), Mode::Max) => value > best + self.min_delta,
+        };
+        if better {
+            self.value = Some(value);
+        }
+        better
+    }
+}
+
+/// Prints the metrics of every `every`-th epoch.
+#[derive(Debug, Clone)]
+pub struct Logger {
+    every: usize,
+}
+
+impl Logger {
+    pub fn new() -> Logger {
+        Logger { every: 1 }
+    }
+
+    pub fn every(mut self, every: usize) -> Logger {
+        assert!(every > 0, "must log at some interval");
+        self.every = every;
+        self
+    }
+}
+
+impl Default for Logger {
+    fn default() -> Logger {
+        Logger::new()
+    }
+}
+
+impl<M: ?Sized> Callback<M> for Logger {
+    fn on_epoch_end(&mut self, epoch: usize, metrics: &Metrics, _model: &M) -> Result<Control> {
+        if epoch.is_multiple_of(self.every) {
+            let line = metrics
+                .iter()
+                .map(|(name, value)| format!("{name} {value:.4}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            println!("epoch {epoch}: {line}");
+        }
+        Ok(Control::Continue)
+    }
+}
+
+/// Saves the model to `path` whenever the monitored metric reaches a new best.
+#[derive(Debug, Clone)]
+pub struct ModelCheckpoint {
+    path: PathBuf,
+    monitor: String,
+    best: Best,
+}
+
+impl ModelCheckpoint {
+    pub fn new(path: impl Into<PathBuf>, monitor: &str) -> ModelCheckpoint {
+        ModelCheckpoint {
+            path: path.into(),
+            monitor: monitor.to_string(),
+            best: Best {
+                mode: Mode::Min,
+                min_delta: 0.0,
+                value: None,
+            },
+        }
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> ModelCheckpoint {
+        self.best.mode = mode;
+        self
+    }
+}
+
+impl Callback<MLP> for ModelCheckpoint {
+    fn on_epoch_end(&mut self, _epoch: usize, metrics: &Metrics, model: &MLP) -> Result<Control> {
+        if self.best.improved(monitored(metrics, &self.monitor)?) {
+            model.save(&self.path)?;
+        }
+        Ok(Control::Continue)
+    }
+
+    fn state_dict(&self) -> Vec<f64> {
+        self.best.state().to_vec()
+    }
+
+    fn load_state_dict(&mut self, state: &[f64]) -> Result<()> {
+        if state.len() != 2 {
+            return Err(format!(
+                "checkpoint callback state has {} values, not 2",
+                state.len()
+            )
+            .into());
+        }
+        self.best.load_state(state);
+        Ok(())
+    }
+}
+
+/// Stops training once the monitored metric has not improved by more than `min_delta` for
+/// `patience` epochs in a row.
+#[derive(Debug, Clone)]
+pub struct EarlyStopping {
+    monitor: String,
+    patience: usize,
+    best: Best,
+    waited: usize,
+}
+
+impl EarlyStopping {
+    pub fn new(monitor: &str, patience: usize) -> EarlyStopping {
+        EarlyStopping {
+            monitor: monitor.to_string(),
+            patience,
+            best: Best {
+                mode: Mode::Min,
+                min_delta: 0.0,
+                value: None,
+            },
+            waited: 0,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: Mode) -> EarlyStopping {
+        self.best.mode = mode;
+        self
+    }
+
+    pub fn with_min_delta(mut self, min_delta: f64) -> EarlyStopping {
+        self.best.min_delta = min_delta;
+        self
+    }
+}
+
+impl<M: ?Sized> Callback<M> for EarlyStopping {
+    fn on_epoch_end(&mut self, _epoch: usize, metrics: &Metrics, _model: &M) -> Result<Control> {
+        if self.best.improved(monitored(metrics, &self.monitor)?) {
+            self.waited = 0;
+        } else {
+            self.waited += 1;
+        }
+        Ok(if self.waited >= self.patience {
+            Control::Stop
+        } else {
+            Control::Continue
+        })
+    }
+
+    /// The best value and the epochs waited since it.
+    fn state_dict(&self) -> Vec<f64> {
+        [self.best.state().as_slice(), &[self.waited as f64]].concat()
+    }
+
+    fn load_state_dict(&mut self, state: &[f64]) -> Result<()> {
+        if state.len() != 3 {
+            return Err(format!("early stopping state has {} values, not 3", state.len()).into());
+        }
+        self.best.load_state(state);
+        self.waited = state[2] as usize;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micrograd::{
+        loss, Activation, Adam, BatchNorm1d, Dropout, Init, Layer, LinearLR, Sequential, StepLR,
+        SGD,
+    };
+    use rand::rngs::StdRng;
+    use std::{env, fs};
+    use uuid::Uuid;
+
+    /// y = 2x - 1 on a few points.
+    fn line(xs: &[f64]) -> InMemoryDataset {
+        InMemoryDataset::new(
+            xs.iter().map(|&x| vec![x]).collect(),
+            xs.iter().map(|&x| vec![2.0 * x - 1.0]).collect(),
+        )
+        .expect("one target per input")
+    }
+
+    fn mse(outputs: &[Vec<Value>], targets: &[Vec<f64>]) -> Value {
+        let preds: Vec<Value> = outputs.iter().map(|row| row[0].clone()).collect();
+        let targets: Vec<f64> = targets.iter().map(|row| row[0]).collect();
+        loss::mse(&preds, &targets, loss::Reduction::Mean)
+    }
+
+    fn linear() -> MLP {
+        MLP::builder(1)
+            .layer_with_init(1, Activation::Identity, Init::Constant(0.0))
+            .build()
+    }
+
+    /// A model in training mode whose outputs depend on dropout generator positions and batch
+    /// norm running stats.
+    fn noisy(seed: u64) -> Sequential {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut model = Sequential::new()
+            .with_module(
+                Layer::with_init(1, 6, Activation::Tanh, Init::XavierUniform, &mut rng)
+                    .with_dropout(Dropout::new(0.2).with_seed(seed)),
+            )
+            .with_module(BatchNorm1d::new(6))
+            .with_module(Layer::with_init(
+                6,
+                1,
+                Activation::Identity,
+                Init::XavierUniform,
+                &mut rng,
+            ));
+        model.train();
+        model
+    }
+
+    #[test]
+    fn fit_records_history() -> Result<()> {
+        let mut model = linear();
+        let validation = line(&[2.0]);
+        let max_error = |out: &[Vec<f64>], t: &[Vec<f64>]| {
+            out.iter()
+                .zip(t)
+                .map(|(o, t)| (o[0] - t[0]).abs())
+                .fold(0.0, f64::max)
+        };
+        let optimizer = SGD::new(model.parameters(), 0.1);
+        let history = Trainer::new(&mut model, optimizer, mse)
+            .with_metric("max_error", max_error)
+            .with_scheduler(StepLR::new(0.1, 50, 0.5))
+            .with_batch_size(2)
+            .with_shuffle(4)
+            .with_validation(&validation)
+            .fit(&line(&[-1.0, -0.5, 0.0, 0.5, 1.0]), 100)?;
+
+        assert_eq!(history.epochs.len(), 100);
+        let keys: Vec<&String> = history.epochs[0].keys().collect();
+        assert_eq!(
+            keys,
+            ["loss", "lr", "max_error", "val_loss", "val_max_error"]
+        );
+        let loss = history.metric("loss");
+        assert!(loss[99] < 1e-6 && loss[99] < loss[0]);
+        assert_eq!(history.metric("lr")[50], 0.05);
+        assert!(history.last().unwrap()["val_max_error"] < 1e-3);
+        Ok(())
+    }
+
+    #[test]
+    fn early_stopping_and_checkpoint() -> Result<()> {
+        let mut model = linear();
+        let dataset = line(&[-1.0, -0.5, 0.0, 0.5, 1.0]);
+        let path = env::temp_dir().join(format!("neural_net_trainer_{}.bin", Uuid::new_v4()));
+        // a learning rate this large diverges, so the loss stops improving right away
+        let optimizer = SGD::new(model.parameters(), 2.0);
+        let history = Trainer::new(&mut model, optimizer, mse)
+            .with_callback(ModelCheckpoint::new(&path, "loss"))
+            .with_callback(EarlyStopping::new("loss", 3))
+            .fit(&dataset, 100)?;
+
+        assert_eq!(history.epochs.len(), 4);
+        let saved = MLP::load(&path)?;
+        fs::remove_file(&path)?;
+        // the first epoch had the best loss, and the model saved after it
+        let weights: Vec<f64> = saved.parameters().iter().map(Value::data).collect();
+        assert_ne!(weights, vec![0.0, 0.0]);
+        assert_ne!(
+            weights,
+            model
+                .parameters()
+                .iter()
+                .map(Value::data)
+                .collect::<Vec<_>>()
+        );
+
+        let optimizer = SGD::new(model.parameters(), 0.1);
+        let mut missing = Trainer::new(&mut model, optimizer, mse)
+            .with_callback(EarlyStopping::new("accuracy", 3).with_mode(Mode::Max));
+        assert!(missing.fit(&dataset, 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn fit_without_data_is_an_error() -> Result<()> {
+        let mut model = linear();
+        let optimizer = SGD::new(model.parameters(), 0.1);
+        let mut trainer = Trainer::new(&mut model, optimizer, mse);
+        assert!(trainer.fit(&line(&[]), 1).is_err());
+        assert_eq!(trainer.epoch(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn fit_trains_more_epochs_each_call() -> Result<()> {
+        let mut model = linear();
+        let dataset = line(&[-1.0, 0.0, 1.0]);
+        let optimizer = SGD::new(model.parameters(), 0.1);
+        let mut trainer =
+            Trainer::new(&mut model, optimizer, mse).with_scheduler(StepLR::new(0.1, 3, 0.5));
+
+        assert_eq!(trainer.fit(&dataset, 2)?.epochs.len(), 2);
+        let history = trainer.fit(&dataset, 2)?;
+        assert_eq!(history.epochs.len(), 2);
+        assert_eq!(history.metric("lr"), [0.1, 0.05]);
+        assert_eq!((trainer.epoch(), trainer.step()), (4, 4));
+
+        assert_eq!(trainer.fit_until(&dataset, 5)?.epochs.len(), 1);
+        assert!(trainer.fit_until(&dataset, 5)?.epochs.is_empty());
+        assert_eq!(trainer.epoch(), 5);
+        Ok(())
+    }
+
+    /// Diverges, so early stopping counts every epoch after the first and stops after the
+    /// fourth.
+    fn diverging(model: &mut MLP) -> Trainer<'_> {
+        let optimizer = SGD::new(model.parameters(), 2.0);
+        Trainer::new(model, optimizer, mse).with_callback(EarlyStopping::new("loss", 3))
+    }
+
+    #[test]
+    fn resume_from_saved_checkpoint_stops_like_uninterrupted() -> Result<()> {
+        let dataset = line(&[-1.0, -0.5, 0.0, 0.5, 1.0]);
+        let path = env::temp_dir().join(format!("neural_net_stopping_{}.bin", Uuid::new_v4()));
+
+        let mut straight = linear();
+        let expected = diverging(&mut straight).fit(&dataset, 100)?;
+        assert_eq!(expected.epochs.len(), 4);
+
+        let mut first = linear();
+        diverging(&mut first)
+            .with_checkpoints(&path, 2)
+            .fit(&dataset, 2)?;
+        let checkpoint = TrainingCheckpoint::load(&path)?;
+        fs::remove_file(&path)?;
+
+        let mut resumed = linear();
+        let mut continued = diverging(&mut resumed);
+        continued.resume(&checkpoint)?;
+        let history = continued.fit_until(&dataset, 100)?;
+        assert_eq!(history.epochs, expected.epochs[2..]);
+        drop(continued);
+        assert_eq!(resumed.state_dict(), straight.state_dict());
+        Ok(())
+    }
+
+    #[test]
+    fn resume_restores_callback_state() -> Result<()> {
+        let dataset = line(&[-1.0, -0.5, 0.0, 0.5, 1.0]);
+        let trainer = diverging;
+
+        let mut first = linear();
+        let mut interrupted = trainer(&mut first);
+        let loss = interrupted.fit(&dataset, 2)?.metric("loss");
+        let checkpoint = interrupted.checkpoint();
+        assert_eq!(checkpoint.callbacks, [vec![1.0, loss[0], 1.0]]);
+        let bytes = checkpoint.to_bytes();
+        assert_eq!(TrainingCheckpoint::from_bytes(&bytes)?, checkpoint);
+
+        let mut resumed = linear();
+        let mut continued = trainer(&mut resumed);
+        continued.resume(&checkpoint)?;
+        assert_eq!(continued.fit_until(&dataset, 100)?.epochs.len(), 2);
+        assert_eq!(continued.epoch(), 4);
+
+        let mut other = linear();
+        let optimizer = SGD::new(other.parameters(), 2.0);
+        let mut without = Trainer::new(&mut other, optimizer, mse);
+        assert!(without.resume(&checkpoint).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn validation_leaves_the_model_untouched() -> Result<()> {
+        let dataset = line(&[-1.0, -0.6, -0.2, 0.2, 0.6, 1.0]);
+        let validation = line(&[0.4, 1.2]);
+
+        let mut plain = noisy(1);
+        let optimizer = Adam::new(plain.parameters(), 0.05);
+        let expected = Trainer::new(&mut plain, optimizer, mse)
+            .with_batch_size(2)
+            .fit(&dataset, 3)?;
+        let mut validated = noisy(1);
+        let optimizer = Adam::new(validated.parameters(), 0.05);
+        let history = Trainer::new(&mut validated, optimizer, mse)
+            .with_batch_size(2)
+            .with_validation(&validation)
+            .fit(&dataset, 3)?;
+
+        assert!(validated.is_training());
+        assert_eq!(history.metric("loss"), expected.metric("loss"));
+        assert_eq!(validated.state_dict(), plain.state_dict());
+        assert_eq!(
+            buffer_states(validated.named_buffers()),
+            buffer_states(plain.named_buffers())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn resumed_training_matches_uninterrupted() -> Result<()> {
+        // dropout and batch norm make the run depend on generator positions and running stats
+        fn trainer(model: &mut Sequential) -> Trainer<'_, Sequential> {
+            let optimizer = Adam::new(model.parameters(), 0.05);
+            Trainer::new(model, optimizer, mse)
+                .with_scheduler(LinearLR::new(0.05, 0.01, 6))
+                .with_batch_size(2)
+                .with_shuffle(3)
+        }
+        let dataset = line(&[-1.0, -0.6, -0.2, 0.2, 0.6, 1.0, 1.4]);
+        let path = env::temp_dir().join(format!("neural_net_resume_{}.bin", Uuid::new_v4()));
+
+        let mut straight = noisy(1);
+        let expected = trainer(&mut straight).fit(&dataset, 6)?;
+
+        let mut first = noisy(1);
+        let mut interrupted = trainer(&mut first).with_checkpoints(&path, 3);
+        interrupted.fit(&dataset, 3)?;
+        assert_eq!((interrupted.epoch(), interrupted.step()), (3, 12));
+        let checkpoint = TrainingCheckpoint::load(&path)?;
+        fs::remove_file(&path)?;
+        assert_eq!(checkpoint, interrupted.checkpoint());
+
+        let mut resumed = noisy(2);
+        let mut continued = trainer(&mut resumed);
+        continued.resume(&checkpoint)?;
+        let history = continued.fit_until(&dataset, 6)?;
+        drop(continued);
+
+        assert_eq!(history.epochs, expected.epochs[3..]);
+        assert_eq!(resumed.state_dict(), straight.state_dict());
+        assert_eq!(
+            buffer_states(resumed.named_buffers()),
+            buffer_states(straight.named_buffers())
+        );
+        let bytes = checkpoint.to_bytes();
+        assert_eq!(TrainingCheckpoint::from_bytes(&bytes)?, checkpoint);
+        assert!(TrainingCheckpoint::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let optimizer = Adam::new(resumed.parameters(), 0.05);
+        let mut unshuffled = Trainer::new(&mut resumed, optimizer, mse);
+        assert!(unshuffled.resume(&checkpoint).is_err());
+        let mut other = linear();
+        let optimizer = Adam::new(other.parameters(), 0.05);
+        let mut mismatched = Trainer::new(&mut other, optimizer, mse).with_shuffle(3);
+        assert!(mismatched.resume(&checkpoint).is_err());
+        Ok(())
+    }
+}
@@ -13,8 +13,11 @@ use crate::micrograd::Value;
 
 use crate::Result;
 
+type Trace = (HashSet<Value>, HashSet<(Value, Value)>);
+
 // use crate::{engine::Value, neural_net::MLP};
 
+#[allow(clippy::mutable_key_type)]
 pub fn visualize_network(network: Vec<Value>, filename: String) -> Result<String> {
     // for multiple roots
     // let label_nodes = network.last_layer();
@@ -29,26 +32,26 @@ pub fn visualize_network(network: Vec<Value>, filename: String) -> Result<String
     }
 
     let dot = make_graph(total_nodes, total_edges)?;
-    let _ = output_graph_file(&dot, filename)?;
+    output_graph_file(&dot, filename)?;
     Ok(dot)
 }
 
-pub fn output_graph_file(dot_graph: &String, filename: String) -> Result<()> {
+pub fn output_graph_file(dot_graph: &str, filename: String) -> Result<()> {
     exec_dot(
-        dot_graph.clone(),
+        dot_graph.to_string(),
         vec![Format::Png.into(), CommandArg::Output(filename)],
     )?;
     Ok(())
 }
 
-fn trace_nodes(root: Value) -> Result<(HashSet<Value>, HashSet<(Value, Value)>)> {
-    let (mut nodes, mut edges): (HashSet<Value>, HashSet<(Value, Value)>) =
-        (HashSet::new(), HashSet::new());
-    fn build<'a>(
-        v: &'a Value,
+#[allow(clippy::mutable_key_type)]
+fn trace_nodes(root: Value) -> Result<Trace> {
+    let (mut nodes, mut edges): Trace = (HashSet::new(), HashSet::new());
+    fn build(
+        v: &Value,
         mut nodes: HashSet<Value>,
         mut edges: HashSet<(Value, Value)>,
-    ) -> Option<(HashSet<Value>, HashSet<(Value, Value)>)> {
+    ) -> Option<Trace> {
         if !nodes.contains(v) {
             nodes.insert(v.clone());
             for child in &v.borrow()._prev {
@@ -67,7 +70,6 @@ fn uuid_to_id(id: Uuid) -> Result<String> {
     Ok(id
         .to_string()
         .split(&"-")
-        .into_iter()
         .last()
         .expect("values")
         .to_owned())
@@ -79,6 +81,7 @@ pub fn draw_dots(root: Value) -> Result<String> {
     make_graph(nodes, edges)
 }
 
+#[allow(clippy::mutable_key_type)]
 fn make_graph(nodes: HashSet<Value>, edges: HashSet<(Value, Value)>) -> Result<String> {
     let mut graph = Graph::DiGraph {
         id: Html(String::from("1")),
@@ -95,7 +98,10 @@ fn make_graph(nodes: HashSet<Value>, edges: HashSet<(Value, Value)>) -> Result<S
             attributes: vec![
                 NodeAttributes::label(format!(
                     "\" {} | data {:?} | grad {:?} \"",
-                    uuid_to_id(n.borrow().uuid)?,
+                    match &n.borrow().label {
+                        Some(label) => label.clone(),
+                        None => uuid_to_id(n.borrow().uuid)?,
+                    },
                     n.borrow().data,
                     n.borrow().grad
                 )),
@@ -103,7 +109,7 @@ fn make_graph(nodes: HashSet<Value>, edges: HashSet<(Value, Value)>) -> Result<S
             ],
         }));
 
-        if n.borrow()._op != None {
+        if n.borrow()._op.is_some() {
             let op_id = NodeId(
                 Html(format!(
                     "\"{}_{:?}\"",
@@ -129,7 +135,7 @@ fn make_graph(nodes: HashSet<Value>, edges: HashSet<(Value, Value)>) -> Result<S
                 Html(format!("\"{}\"", uuid_to_id(n1.borrow().uuid)?)),
                 None,
             ));
-            let n2_string = if n2.borrow()._op.as_ref() != None {
+            let n2_string = if n2.borrow()._op.is_some() {
                 format!(
                     "\"{}_{:?}\"",
                     uuid_to_id(n2.borrow().uuid)?,
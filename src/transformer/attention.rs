@@ -0,0 +1,260 @@
+use rand::Rng;
+
+use crate::micrograd::{module::prefixed, softmax, Activation, Init, Layer, Module, Value};
+
+/// One head of scaled dot-product self-attention over a sequence of vectors.
+///
+/// Each position's output is the average of every position's value, weighted by the softmax
+/// of its query's dot products with their keys divided by `sqrt(head_dim)`. With a causal
+/// mask a position attends only to itself and earlier ones.
+#[derive(Debug)]
+pub struct SelfAttention {
+    query: Layer,
+    key: Layer,
+    value: Layer,
+    causal: bool,
+}
+
+impl SelfAttention {
+    pub fn new(dim: usize, head_dim: usize, rng: &mut impl Rng) -> SelfAttention {
+        let mut projection = || {
+            Layer::with_init(
+                dim,
+                head_dim,
+                Activation::Identity,
+                Init::XavierUniform,
+                rng,
+            )
+        };
+        SelfAttention::from_layers(projection(), projection(), projection())
+    }
+
+    /// A head with the given query, key and value projections, which must have the same sizes.
+    pub fn from_layers(query: Layer, key: Layer, value: Layer) -> SelfAttention {
+        SelfAttention {
+            query,
+            key,
+            value,
+            causal: false,
+        }
+    }
+
+    /// Masks out later positions, so outputs never depend on tokens that come after them.
+    pub fn with_causal(mut self, causal: bool) -> SelfAttention {
+        self.causal = causal;
+        self
+    }
+
+    pub fn is_causal(&self) -> bool {
+        self.causal
+    }
+
+    pub fn forward(&self, sequence: &[Vec<Value>]) -> Vec<Vec<Value>> {
+        let queries = self.query.forward_batch(sequence);
+        let keys = self.key.forward_batch(sequence);
+        let values = self.value.forward_batch(sequence);
+        let scale = Value::from(1.0 / (self.key.neurons.len() as f64).sqrt());
+        queries
+            .iter()
+            .enumerate()
+            .map(|(i, query)| {
+                let visible = if self.causal { i + 1 } else { keys.len() };
+                let scores: Vec<Value> = keys[..visible]
+                    .iter()
+                    .map(|key| &dot(query, key) * &scale)
+                    .collect();
+                let weights = softmax(&scores);
+                (0..values[0].len())
+                    .map(|k| {
+                        weights
+                            .iter()
+                            .zip(&values)
+                            .map(|(w, value)| w * &value[k])
+                            .sum()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Treats the input as a sequence of one position.
+impl Module for SelfAttention {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        SelfAttention::forward(self, &[input.to_vec()]).remove(0)
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        [
+            prefixed("query", self.query.named_parameters()),
+            prefixed("key", self.key.named_parameters()),
+            prefixed("value", self.value.named_parameters()),
+        ]
+        .concat()
+    }
+}
+
+/// Several attention heads side by side, their outputs concatenated and projected back to
+/// `dim` values.
+#[derive(Debug)]
+pub struct MultiHeadAttention {
+    heads: Vec<SelfAttention>,
+    output: Layer,
+}
+
+impl MultiHeadAttention {
+    /// `n_heads` heads of `dim / n_heads` values each.
+    pub fn new(dim: usize, n_heads: usize, rng: &mut impl Rng) -> MultiHeadAttention {
+        assert!(
+            n_heads > 0 && dim.is_multiple_of(n_heads),
+            "dim {dim} must split evenly into {n_heads} heads"
+        );
+        let heads = (0..n_heads)
+            .map(|_| SelfAttention::new(dim, dim / n_heads, rng))
+            .collect();
+        let output = Layer::with_init(dim, dim, Activation::Identity, Init::XavierUniform, rng);
+        MultiHeadAttention::from_heads(heads, output)
+    }
+
+    pub fn from_heads(heads: Vec<SelfAttention>, output: Layer) -> MultiHeadAttention {
+        MultiHeadAttention { heads, output }
+    }
+
+    /// Sets the causal mask of every head.
+    pub fn with_causal(mut self, causal: bool) -> MultiHeadAttention {
+        self.heads = self
+            .heads
+            .into_iter()
+            .map(|head| head.with_causal(causal))
+            .collect();
+        self
+    }
+
+    pub fn heads(&self) -> &[SelfAttention] {
+        &self.heads
+    }
+
+    pub fn forward(&self, sequence: &[Vec<Value>]) -> Vec<Vec<Value>> {
+        let per_head: Vec<Vec<Vec<Value>>> = self
+            .heads
+            .iter()
+            .map(|head| head.forward(sequence))
+            .collect();
+        let concatenated: Vec<Vec<Value>> = (0..sequence.len())
+            .map(|i| per_head.iter().flat_map(|head| head[i].clone()).collect())
+            .collect();
+        self.output.forward_batch(&concatenated)
+    }
+}
+
+/// Treats the input as a sequence of one position.
+impl Module for MultiHeadAttention {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        MultiHeadAttention::forward(self, &[input.to_vec()]).remove(0)
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        let heads = self
+            .heads
+            .iter()
+            .enumerate()
+            .flat_map(|(h, head)| prefixed(&format!("heads.{h}"), head.named_parameters()));
+        heads
+            .chain(prefixed("output", self.output.named_parameters()))
+            .collect()
+    }
+}
+
+fn dot(a: &[Value], b: &[Value]) -> Value {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micrograd::Neuron;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn identity(dim: usize) -> Layer {
+        Layer::from(
+            (0..dim)
+                .map(|i| {
+                    let weights = (0..dim).map(|j| if i == j { 1.0 } else { 0.0 }).collect();
+                    Neuron::from((weights, 0.0, false))
+                })
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    fn sequence(rows: &[&[f64]]) -> Vec<Vec<Value>> {
+        rows.iter()
+            .map(|row| row.iter().map(|&x| Value::from(x)).collect())
+            .collect()
+    }
+
+    fn data(rows: &[Vec<Value>]) -> Vec<Vec<f64>> {
+        rows.iter()
+            .map(|row| row.iter().map(Value::data).collect())
+            .collect()
+    }
+
+    fn assert_close(actual: &[Vec<Value>], expected: &[Vec<f64>]) {
+        for (a, e) in data(actual).iter().flatten().zip(expected.iter().flatten()) {
+            assert!((a - e).abs() < 1e-12, "{:?} vs {expected:?}", data(actual));
+        }
+    }
+
+    #[test]
+    fn attention_matches_hand_computed_weights() {
+        let head = SelfAttention::from_layers(identity(2), identity(2), identity(2));
+        let x = sequence(&[&[1.0, 0.0], &[0.0, 1.0]]);
+        // each position scores 1/sqrt(2) against itself and 0 against the other
+        let w = 1.0 / (1.0 + (-(0.5f64).sqrt()).exp());
+        assert_close(&head.forward(&x), &[vec![w, 1.0 - w], vec![1.0 - w, w]]);
+
+        let causal = head.with_causal(true);
+        assert!(causal.is_causal());
+        assert_close(&causal.forward(&x), &[vec![1.0, 0.0], vec![1.0 - w, w]]);
+    }
+
+    #[test]
+    fn causal_outputs_ignore_later_positions() {
+        let attention =
+            MultiHeadAttention::new(4, 2, &mut StdRng::seed_from_u64(3)).with_causal(true);
+        let first = sequence(&[&[0.1, 0.2, 0.3, 0.4], &[1.0, -1.0, 0.5, 0.0]]);
+        let second = sequence(&[&[0.1, 0.2, 0.3, 0.4], &[-2.0, 0.7, 0.0, 3.0]]);
+        let (a, b) = (attention.forward(&first), attention.forward(&second));
+        assert_eq!(data(&a)[0], data(&b)[0]);
+        assert_ne!(data(&a)[1], data(&b)[1]);
+        assert_eq!(attention.parameters().len(), 2 * 3 * 2 * 5 + 4 * 5);
+        assert_eq!(
+            attention.named_parameters()[10].0,
+            "heads.0.key.neurons.0.bias"
+        );
+    }
+
+    #[test]
+    fn heads_are_concatenated_before_the_output_projection() {
+        let head = |rows: [[f64; 2]; 1]| {
+            let layer = || Layer::from(vec![Neuron::from((rows[0].to_vec(), 0.0, false))]);
+            SelfAttention::from_layers(layer(), layer(), layer())
+        };
+        // the output projection swaps the two heads' values
+        let swap = Layer::from(vec![
+            Neuron::from((vec![0.0, 1.0], 0.0, false)),
+            Neuron::from((vec![1.0, 0.0], 0.0, false)),
+        ]);
+        let attention =
+            MultiHeadAttention::from_heads(vec![head([[1.0, 0.0]]), head([[0.0, 2.0]])], swap);
+        // a single position attends only to itself
+        let out = attention.forward(&sequence(&[&[3.0, 5.0]]));
+        assert_close(&out, &[vec![10.0, 3.0]]);
+        assert_eq!(
+            data(&[Module::forward(
+                &attention,
+                &[Value::from(3.0), Value::from(5.0)]
+            )]),
+            [[10.0, 3.0]]
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use rand::Rng;
+
+use super::MultiHeadAttention;
+use crate::micrograd::{module::prefixed, Activation, Init, Layer, LayerNorm, Module, Value};
+
+/// Two layers applied to each position on its own, widening to `hidden` values with a ReLU
+/// and projecting back to `dim`.
+#[derive(Debug)]
+pub struct FeedForward {
+    hidden: Layer,
+    output: Layer,
+}
+
+impl FeedForward {
+    pub fn new(dim: usize, hidden: usize, rng: &mut impl Rng) -> FeedForward {
+        FeedForward::from_layers(
+            Layer::with_init(dim, hidden, Activation::Relu, Init::HeUniform, rng),
+            Layer::with_init(hidden, dim, Activation::Identity, Init::XavierUniform, rng),
+        )
+    }
+
+    pub fn from_layers(hidden: Layer, output: Layer) -> FeedForward {
+        FeedForward { hidden, output }
+    }
+}
+
+impl Module for FeedForward {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        self.output.forward(&self.hidden.forward(input))
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        [
+            prefixed("hidden", self.hidden.named_parameters()),
+            prefixed("output", self.output.named_parameters()),
+        ]
+        .concat()
+    }
+}
+
+/// Attention followed by a feed-forward network, each applied to a layer-normalized copy of
+/// its input and added back onto it:
+///
+/// ```text
+/// x = x + attention(norm1(x))
+/// x = x + feed_forward(norm2(x))
+/// ```
+#[derive(Debug)]
+pub struct TransformerBlock {
+    norm1: LayerNorm,
+    attention: MultiHeadAttention,
+    norm2: LayerNorm,
+    feed_forward: FeedForward,
+}
+
+impl TransformerBlock {
+    /// A block of `n_heads` attention heads and a feed-forward network four times as wide as
+    /// `dim`.
+    pub fn new(dim: usize, n_heads: usize, rng: &mut impl Rng) -> TransformerBlock {
+        TransformerBlock::from_parts(
+            MultiHeadAttention::new(dim, n_heads, rng),
+            FeedForward::new(dim, 4 * dim, rng),
+        )
+    }
+
+    pub fn from_parts(
+        attention: MultiHeadAttention,
+        feed_forward: FeedForward,
+    ) -> TransformerBlock {
+        let dim = feed_forward.output.neurons.len();
+        TransformerBlock {
+            norm1: LayerNorm::new(dim),
+            attention,
+            norm2: LayerNorm::new(dim),
+            feed_forward,
+        }
+    }
+
+    /// Sets the causal mask of every attention head.
+    pub fn with_causal(mut self, causal: bool) -> TransformerBlock {
+        self.attention = self.attention.with_causal(causal);
+        self
+    }
+
+    pub fn forward(&self, sequence: &[Vec<Value>]) -> Vec<Vec<Value>> {
+        let attended = self.attention.forward(&self.norm1.forward_batch(sequence));
+        let x: Vec<Vec<Value>> = sequence
+            .iter()
+            .zip(&attended)
+            .map(|(x, a)| add(x, a))
+            .collect();
+        x.iter()
+            .map(|x| add(x, &self.feed_forward.forward(&self.norm2.forward(x))))
+            .collect()
+    }
+}
+
+/// Treats the input as a sequence of one position.
+impl Module for TransformerBlock {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        TransformerBlock::forward(self, &[input.to_vec()]).remove(0)
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        [
+            prefixed("norm1", self.norm1.named_parameters()),
+            prefixed("attention", self.attention.named_parameters()),
+            prefixed("norm2", self.norm2.named_parameters()),
+            prefixed("feed_forward", self.feed_forward.named_parameters()),
+        ]
+        .concat()
+    }
+}
+
+fn add(a: &[Value], b: &[Value]) -> Vec<Value> {
+    a.iter().zip(b).map(|(x, y)| x + y).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micrograd::Neuron;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn sequence(rows: &[&[f64]]) -> Vec<Vec<Value>> {
+        rows.iter()
+            .map(|row| row.iter().map(|&x| Value::from(x)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn feed_forward_matches_hand_computed_output() {
+        let feed_forward = FeedForward::from_layers(
+            Layer::from(vec![
+                Neuron::from((vec![1.0, 1.0], 0.0, true)),
+                Neuron::from((vec![1.0, -1.0], 0.0, true)),
+            ]),
+            Layer::from(vec![
+                Neuron::from((vec![2.0, 0.0], 1.0, false)),
+                Neuron::from((vec![0.0, 3.0], 0.0, false)),
+            ]),
+        );
+        // hidden relu([2 + 3, 2 - 3]) = [5, 0]
+        let out = feed_forward.forward(&[Value::from(2.0), Value::from(3.0)]);
+        assert_eq!(out.iter().map(Value::data).collect::<Vec<_>>(), [11.0, 0.0]);
+    }
+
+    #[test]
+    fn residuals_pass_input_through_zeroed_sublayers() {
+        let block = TransformerBlock::new(4, 2, &mut StdRng::seed_from_u64(5)).with_causal(true);
+        assert_eq!(
+            block.parameters().len(),
+            2 * 8 + (2 * 3 * 2 * 5 + 4 * 5) + (16 * 5 + 4 * 17)
+        );
+        let x = sequence(&[&[0.5, -1.0, 2.0, 0.0], &[1.0, 1.0, -3.0, 0.25]]);
+        let changed = block.forward(&x);
+        assert_ne!(changed[0][0].data(), 0.5);
+
+        for (name, p) in block.named_parameters() {
+            if name.contains(".output.") {
+                p.borrow_mut().data = 0.0;
+            }
+        }
+        let out = block.forward(&x);
+        for (row, input) in out.iter().zip(&x) {
+            let row: Vec<f64> = row.iter().map(Value::data).collect();
+            let input: Vec<f64> = input.iter().map(Value::data).collect();
+            assert_eq!(row, input);
+        }
+    }
+
+    #[test]
+    fn gradients_flow_to_every_parameter() {
+        let block = TransformerBlock::new(4, 2, &mut StdRng::seed_from_u64(8)).with_causal(true);
+        let x = sequence(&[
+            &[0.5, -1.0, 2.0, 0.0],
+            &[1.0, 1.0, -3.0, 0.25],
+            &[0.0, 2.0, 1.0, -1.0],
+        ]);
+        let loss: Value = block
+            .forward(&x)
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(k, out)| out * &Value::from(0.7 * k as f64 - 2.0))
+            .sum();
+        loss.backward();
+        let silent: Vec<String> = block
+            .named_parameters()
+            .into_iter()
+            .filter(|(_, p)| p.grad() == 0.0)
+            .map(|(name, _)| name)
+            .collect();
+        // a relu unit may be inactive for every position, but not most of them, and softmax
+        // cancels a key bias shared by all positions
+        let excused = |name: &String| {
+            name.starts_with("feed_forward.hidden")
+                || name.contains(".key.") && name.ends_with("bias")
+        };
+        assert!(silent.iter().all(excused), "{silent:?}");
+        assert!(silent.len() < 20, "{silent:?}");
+    }
+}
@@ -0,0 +1,218 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::micrograd::{Init, Module, Value};
+
+/// A trainable vector of `dim` values for each of `vocab_size` token IDs.
+///
+/// Looking a token up hands out the parameters themselves, so backpropagation accumulates
+/// gradient only into the rows of the tokens that were used.
+#[derive(Debug)]
+pub struct Embedding {
+    weight: Vec<Vec<Value>>,
+}
+
+impl Embedding {
+    /// Rows drawn from a standard normal distribution.
+    pub fn new(vocab_size: usize, dim: usize) -> Embedding {
+        Embedding::with_init(
+            vocab_size,
+            dim,
+            Init::Normal(0.0, 1.0),
+            &mut StdRng::from_entropy(),
+        )
+    }
+
+    pub fn with_init(vocab_size: usize, dim: usize, init: Init, rng: &mut impl Rng) -> Embedding {
+        Embedding {
+            weight: init
+                .weights(dim, vocab_size, rng)
+                .into_iter()
+                .map(|row| row.into_iter().map(Value::from).collect())
+                .collect(),
+        }
+    }
+
+    pub fn vocab_size(&self) -> usize {
+        self.weight.len()
+    }
+
+    pub fn dim(&self) -> usize {
+        self.weight.first().map_or(0, Vec::len)
+    }
+
+    /// The vector of each token in `ids`.
+    pub fn forward(&self, ids: &[usize]) -> Vec<Vec<Value>> {
+        ids.iter()
+            .map(|&id| {
+                assert!(
+                    id < self.vocab_size(),
+                    "token id {id} is outside the vocabulary of {}",
+                    self.vocab_size()
+                );
+                self.weight[id].clone()
+            })
+            .collect()
+    }
+
+    /// An output projection that shares this embedding's weights, mapping vectors of `dim`
+    /// values to one logit per token.
+    pub fn tied_projection(&self) -> Projection {
+        Projection {
+            weight: self.weight.clone(),
+            tied: true,
+        }
+    }
+}
+
+/// Takes the token IDs as the data of its inputs and returns their vectors one after the
+/// other, so a window of tokens becomes a single row.
+impl Module for Embedding {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        Embedding::forward(self, &token_ids(input)).concat()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        named_rows(&self.weight)
+    }
+}
+
+/// Maps a vector of `dim` values to one logit per token of a vocabulary.
+///
+/// A projection made with [`Embedding::tied_projection`] reuses the embedding's weights and
+/// reports no parameters of its own, so optimizers update the shared weights once.
+#[derive(Debug)]
+pub struct Projection {
+    weight: Vec<Vec<Value>>,
+    tied: bool,
+}
+
+impl Projection {
+    /// A projection with its own weights drawn from `init`.
+    pub fn with_init(dim: usize, vocab_size: usize, init: Init, rng: &mut impl Rng) -> Projection {
+        Projection {
+            weight: Embedding::with_init(vocab_size, dim, init, rng).weight,
+            tied: false,
+        }
+    }
+
+    pub fn is_tied(&self) -> bool {
+        self.tied
+    }
+}
+
+impl Module for Projection {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        self.weight
+            .iter()
+            .map(|row| {
+                assert_eq!(row.len(), input.len(), "input must have `dim` values");
+                row.iter().zip(input).map(|(w, x)| w * x).sum()
+            })
+            .collect()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        if self.tied {
+            Vec::new()
+        } else {
+            named_rows(&self.weight)
+        }
+    }
+}
+
+/// The token IDs held as the data of `input`, each of which must be a non-negative integer.
+pub(crate) fn token_ids(input: &[Value]) -> Vec<usize> {
+    input
+        .iter()
+        .map(|id| {
+            let id = id.data();
+            assert!(
+                id >= 0.0 && id.fract() == 0.0,
+                "token id {id} is not a non-negative integer"
+            );
+            id as usize
+        })
+        .collect()
+}
+
+fn named_rows(weight: &[Vec<Value>]) -> Vec<(String, Value)> {
+    weight
+        .iter()
+        .enumerate()
+        .flat_map(|(id, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(k, w)| (format!("weight.{id}.{k}"), w.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embedding() -> Embedding {
+        Embedding::with_init(5, 3, Init::Normal(0.0, 1.0), &mut StdRng::seed_from_u64(1))
+    }
+
+    #[test]
+    fn gradients_reach_only_used_rows() {
+        let embedding = embedding();
+        let vectors = embedding.forward(&[3, 1, 3]);
+        assert_eq!(vectors.len(), 3);
+        assert_eq!(vectors[0], embedding.weight[3]);
+
+        let loss: Value = vectors.iter().flatten().cloned().sum();
+        loss.backward();
+        for (id, row) in embedding.weight.iter().enumerate() {
+            let expected = match id {
+                3 => 2.0,
+                1 => 1.0,
+                _ => 0.0,
+            };
+            assert!(row.iter().all(|w| w.grad() == expected), "row {id}");
+        }
+
+        let window = Module::forward(&embedding, &[Value::from(4.0), Value::from(0.0)]);
+        assert_eq!(
+            window,
+            [embedding.weight[4].clone(), embedding.weight[0].clone()].concat()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "token id 1.5 is not a non-negative integer")]
+    fn fractional_ids_are_rejected() {
+        Module::forward(&embedding(), &[Value::from(1.5)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "token id -1 is not a non-negative integer")]
+    fn negative_ids_are_rejected() {
+        Module::forward(&embedding(), &[Value::from(-1.0)]);
+    }
+
+    #[test]
+    fn tied_projection_shares_weights() {
+        let embedding = embedding();
+        let projection = embedding.tied_projection();
+        assert!(projection.is_tied());
+        assert!(projection.parameters().is_empty());
+        assert_eq!(embedding.parameters().len(), 15);
+
+        let hidden = &embedding.forward(&[2])[0];
+        let logits = projection.forward(hidden);
+        assert_eq!(logits.len(), 5);
+        let norm: f64 = hidden.iter().map(|x| x.data().powi(2)).sum();
+        assert!((logits[2].data() - norm).abs() < 1e-12);
+
+        // row 2 is reached through the lookup and the projection
+        logits[2].backward();
+        let w = &embedding.weight[2][0];
+        assert!((w.grad() - 2.0 * w.data()).abs() < 1e-12);
+
+        let own = Projection::with_init(3, 5, Init::Constant(0.5), &mut StdRng::seed_from_u64(1));
+        assert!(!own.is_tied());
+        assert_eq!(own.named_parameters()[4].0, "weight.1.1");
+    }
+}
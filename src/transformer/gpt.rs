@@ -0,0 +1,407 @@
+use std::cmp::Ordering;
+
+use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, SeedableRng};
+
+use super::{embedding::token_ids, Embedding, Projection, TransformerBlock};
+use crate::micrograd::{
+    loss::{cross_entropy, Reduction},
+    module::prefixed,
+    Init, LayerNorm, Module, Value,
+};
+use crate::Result;
+
+/// A decoder-only transformer predicting the next token of a sequence.
+///
+/// Token and position embeddings are added, passed through causal transformer blocks and a
+/// final layer norm, and projected to one logit per token by the LM head.
+#[derive(Debug)]
+pub struct GPT {
+    token: Embedding,
+    position: Embedding,
+    blocks: Vec<TransformerBlock>,
+    norm: LayerNorm,
+    head: Projection,
+}
+
+impl GPT {
+    /// Starts a model over `vocab_size` tokens that reads at most `context` tokens at a time.
+    pub fn builder(vocab_size: usize, context: usize) -> GPTBuilder {
+        GPTBuilder {
+            vocab_size,
+            context,
+            dim: 16,
+            n_heads: 2,
+            n_layers: 2,
+            tied: true,
+            seed: None,
+        }
+    }
+
+    pub fn context(&self) -> usize {
+        self.position.vocab_size()
+    }
+
+    pub fn vocab_size(&self) -> usize {
+        self.token.vocab_size()
+    }
+
+    /// The next-token logits after every position of `ids`.
+    pub fn forward(&self, ids: &[usize]) -> Vec<Vec<Value>> {
+        assert!(
+            ids.len() <= self.context(),
+            "{} tokens do not fit the context of {}",
+            ids.len(),
+            self.context()
+        );
+        let positions: Vec<usize> = (0..ids.len()).collect();
+        let mut x: Vec<Vec<Value>> = self
+            .token
+            .forward(ids)
+            .iter()
+            .zip(self.position.forward(&positions))
+            .map(|(token, position)| token.iter().zip(&position).map(|(t, p)| t + p).collect())
+            .collect();
+        for block in &self.blocks {
+            x = block.forward(&x);
+        }
+        x.iter()
+            .map(|x| self.head.forward(&self.norm.forward(x)))
+            .collect()
+    }
+
+    /// Mean cross-entropy of predicting each token of `ids` from the ones before it.
+    pub fn loss(&self, ids: &[usize]) -> Value {
+        assert!(ids.len() >= 2, "need at least one token to predict");
+        let logits = self.forward(&ids[..ids.len() - 1]);
+        cross_entropy(&logits, &ids[1..], Reduction::Mean)
+    }
+
+    /// Extends `prompt` by up to `max_tokens` tokens drawn by `sampler`, reading only the last
+    /// `context` tokens at each step. Returns the prompt and the new tokens, or an error for an
+    /// empty prompt or logits nothing can be sampled from.
+    pub fn generate(
+        &self,
+        prompt: &[usize],
+        max_tokens: usize,
+        sampler: &mut Sampler,
+    ) -> Result<Vec<usize>> {
+        if prompt.is_empty() {
+            return Err("prompt must not be empty".into());
+        }
+        let mut ids = prompt.to_vec();
+        for _ in 0..max_tokens {
+            let window = &ids[ids.len().saturating_sub(self.context())..];
+            let logits: Vec<f64> = self.forward(window)[window.len() - 1]
+                .iter()
+                .map(Value::data)
+                .collect();
+            ids.push(sampler.sample(&logits)?);
+        }
+        Ok(ids)
+    }
+}
+
+/// Takes the token IDs as the data of its inputs and returns the logits of the token after
+/// the last one.
+impl Module for GPT {
+    fn forward(&self, input: &[Value]) -> Vec<Value> {
+        GPT::forward(self, &token_ids(input))
+            .pop()
+            .unwrap_or_default()
+    }
+
+    fn named_parameters(&self) -> Vec<(String, Value)> {
+        let blocks = self
+            .blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(i, block)| prefixed(&format!("blocks.{i}"), block.named_parameters()));
+        prefixed("token", self.token.named_parameters())
+            .into_iter()
+            .chain(prefixed("position", self.position.named_parameters()))
+            .chain(blocks)
+            .chain(prefixed("norm", self.norm.named_parameters()))
+            .chain(prefixed("head", self.head.named_parameters()))
+            .collect()
+    }
+}
+
+/// Sizes of a `GPT`, 16 dimensions, 2 heads and 2 blocks with a tied LM head unless set.
+#[derive(Debug, Clone)]
+pub struct GPTBuilder {
+    vocab_size: usize,
+    context: usize,
+    dim: usize,
+    n_heads: usize,
+    n_layers: usize,
+    tied: bool,
+    seed: Option<u64>,
+}
+
+impl GPTBuilder {
+    pub fn with_dim(mut self, dim: usize) -> GPTBuilder {
+        self.dim = dim;
+        self
+    }
+
+    pub fn with_heads(mut self, n_heads: usize) -> GPTBuilder {
+        self.n_heads = n_heads;
+        self
+    }
+
+    pub fn with_layers(mut self, n_layers: usize) -> GPTBuilder {
+        self.n_layers = n_layers;
+        self
+    }
+
+    /// Whether the LM head shares the token embedding's weights.
+    pub fn with_tied_weights(mut self, tied: bool) -> GPTBuilder {
+        self.tied = tied;
+        self
+    }
+
+    /// Draws all weights from a generator seeded with `seed`, so builds repeat exactly.
+    pub fn with_seed(mut self, seed: u64) -> GPTBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn build(self) -> GPT {
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let embedding = Init::Normal(0.0, 0.1);
+        let token = Embedding::with_init(self.vocab_size, self.dim, embedding, &mut rng);
+        let position = Embedding::with_init(self.context, self.dim, embedding, &mut rng);
+        let blocks = (0..self.n_layers)
+            .map(|_| TransformerBlock::new(self.dim, self.n_heads, &mut rng).with_causal(true))
+            .collect();
+        let head = if self.tied {
+            token.tied_projection()
+        } else {
+            Projection::with_init(self.dim, self.vocab_size, embedding, &mut rng)
+        };
+        GPT {
+            token,
+            position,
+            blocks,
+            norm: LayerNorm::new(self.dim),
+            head,
+        }
+    }
+}
+
+/// Draws a token from next-token logits.
+///
+/// The logits are divided by the temperature, then only the `top_k` most likely tokens and
+/// the smallest set of most likely tokens whose probability reaches `top_p` are kept. A
+/// temperature of 0 always picks the most likely token.
+#[derive(Debug)]
+pub struct Sampler {
+    temperature: f64,
+    top_k: Option<usize>,
+    top_p: Option<f64>,
+    rng: StdRng,
+}
+
+impl Sampler {
+    /// Samples from the unmodified distribution.
+    pub fn new() -> Sampler {
+        Sampler {
+            temperature: 1.0,
+            top_k: None,
+            top_p: None,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    pub fn with_temperature(mut self, temperature: f64) -> Sampler {
+        assert!(temperature >= 0.0, "temperature must not be negative");
+        self.temperature = temperature;
+        self
+    }
+
+    pub fn with_top_k(mut self, k: usize) -> Sampler {
+        assert!(k > 0, "top-k must keep at least one token");
+        self.top_k = Some(k);
+        self
+    }
+
+    pub fn with_top_p(mut self, p: f64) -> Sampler {
+        assert!(p > 0.0 && p <= 1.0, "top-p must be in (0, 1]");
+        self.top_p = Some(p);
+        self
+    }
+
+    /// Draws from a generator seeded with `seed`, so samples repeat exactly.
+    pub fn with_seed(mut self, seed: u64) -> Sampler {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// The probability of each token after temperature, top-k and top-p are applied.
+    ///
+    /// Logits of `-inf` mask their tokens out. It is an error if there are no logits, any is NaN
+    /// or `+inf`, or all are `-inf`.
+    pub fn probabilities(&self, logits: &[f64]) -> Result<Vec<f64>> {
+        if let Some(logit) = logits.iter().find(|l| l.is_nan() || **l == f64::INFINITY) {
+            return Err(format!("cannot sample from a logit of {logit}").into());
+        }
+        let mut order: Vec<usize> = (0..logits.len()).collect();
+        order.sort_by(|&a, &b| logits[b].partial_cmp(&logits[a]).unwrap_or(Ordering::Equal));
+        let max = match order.first() {
+            Some(&best) if logits[best] > f64::NEG_INFINITY => logits[best],
+            Some(_) => return Err("every token is masked out by a logit of -inf".into()),
+            None => return Err("no logits to sample from".into()),
+        };
+        let mut probs = vec![0.0; logits.len()];
+        if self.temperature == 0.0 {
+            probs[order[0]] = 1.0;
+            return Ok(probs);
+        }
+
+        let keep = self.top_k.unwrap_or(logits.len()).min(logits.len());
+        for &i in &order[..keep] {
+            probs[i] = ((logits[i] - max) / self.temperature).exp();
+        }
+        normalize(&mut probs);
+        if let Some(top_p) = self.top_p {
+            let mut total = 0.0;
+            for &i in &order {
+                if total >= top_p {
+                    probs[i] = 0.0;
+                }
+                total += probs[i];
+            }
+            normalize(&mut probs);
+        }
+        Ok(probs)
+    }
+
+    /// Draws a token from the [`probabilities`](Sampler::probabilities) of `logits`.
+    pub fn sample(&mut self, logits: &[f64]) -> Result<usize> {
+        let probs = self.probabilities(logits)?;
+        Ok(WeightedIndex::new(&probs)?.sample(&mut self.rng))
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Sampler {
+        Sampler::new()
+    }
+}
+
+fn normalize(probs: &mut [f64]) {
+    let total: f64 = probs.iter().sum();
+    probs.iter_mut().for_each(|p| *p /= total);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::micrograd::{Adam, Optimizer};
+
+    #[test]
+    fn sampling_strategies_filter_tokens() -> Result<()> {
+        let logits = [2.0, 1.0, 0.0, (0.5f64).ln() + 2.0];
+        let close = |a: &[f64], b: &[f64]| a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-12);
+
+        let e = std::f64::consts::E;
+        let total = 1.0 + 0.5 + 1.0 / e + 1.0 / (e * e);
+        let full = [1.0, 1.0 / e, 1.0 / (e * e), 0.5].map(|p| p / total);
+        let probs = Sampler::new().probabilities(&logits)?;
+        assert!(close(&probs, &full));
+
+        let greedy = Sampler::new().with_temperature(0.0);
+        assert_eq!(greedy.probabilities(&logits)?, [1.0, 0.0, 0.0, 0.0]);
+        let top_k = Sampler::new().with_top_k(2).probabilities(&logits)?;
+        assert!(close(&top_k, &[2.0 / 3.0, 0.0, 0.0, 1.0 / 3.0]));
+        // of the top three, the first token alone has probability 0.54, short of 0.7
+        let top_p = Sampler::new()
+            .with_top_k(3)
+            .with_top_p(0.7)
+            .probabilities(&logits)?;
+        assert!(close(&top_p, &[2.0 / 3.0, 0.0, 0.0, 1.0 / 3.0]));
+        let cold = Sampler::new()
+            .with_temperature(0.5)
+            .probabilities(&logits)?;
+        assert!(cold[0] > probs[0] && cold[2] < probs[2]);
+
+        let draws = |seed| {
+            let mut sampler = Sampler::new().with_seed(seed);
+            (0..20)
+                .map(|_| sampler.sample(&logits))
+                .collect::<Result<Vec<_>>>()
+        };
+        assert_eq!(draws(4)?, draws(4)?);
+        assert_eq!(
+            Sampler::new().with_seed(1).with_top_k(1).sample(&logits)?,
+            0
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unsampleable_logits_are_errors() -> Result<()> {
+        let mut sampler = Sampler::new().with_seed(1);
+        let inf = f64::INFINITY;
+        assert_eq!(sampler.sample(&[-inf, 0.5, -inf])?, 1);
+        for logits in [
+            vec![],
+            vec![0.0, f64::NAN],
+            vec![inf, 0.0],
+            vec![-inf, -inf],
+        ] {
+            assert!(sampler.sample(&logits).is_err(), "{logits:?}");
+            let greedy = Sampler::new().with_temperature(0.0);
+            assert!(greedy.probabilities(&logits).is_err(), "{logits:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn generating_from_an_empty_prompt_is_an_error() {
+        let gpt = GPT::builder(3, 6).with_dim(8).with_seed(2).build();
+        assert!(gpt.generate(&[], 4, &mut Sampler::new()).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "token id -2 is not a non-negative integer")]
+    fn module_forward_rejects_negative_ids() {
+        let gpt = GPT::builder(3, 6).with_dim(8).with_seed(2).build();
+        Module::forward(&gpt, &[Value::from(0.0), Value::from(-2.0)]);
+    }
+
+    #[test]
+    fn training_lowers_loss_and_generation_follows() -> Result<()> {
+        let gpt = GPT::builder(3, 6).with_dim(8).with_seed(2).build();
+        assert!(gpt
+            .named_parameters()
+            .iter()
+            .all(|(name, _)| !name.starts_with("head")));
+        let sequence = [0, 1, 2, 0, 1, 2];
+        let mut optimizer = Adam::new(gpt.parameters(), 0.05);
+        let mut losses = Vec::new();
+        for _ in 0..30 {
+            let loss = gpt.loss(&sequence);
+            optimizer.zero_grad();
+            loss.backward();
+            optimizer.step();
+            losses.push(loss.data());
+        }
+        assert!(losses[29] < losses[0] / 4.0, "{losses:?}");
+
+        let mut sampler = Sampler::new().with_temperature(0.0);
+        assert_eq!(gpt.generate(&[0, 1], 4, &mut sampler)?, [0, 1, 2, 0, 1, 2]);
+        let logits = Module::forward(&gpt, &[Value::from(0.0)]);
+        assert_eq!(logits.len(), 3);
+
+        let untied = GPT::builder(3, 6)
+            .with_dim(8)
+            .with_tied_weights(false)
+            .build();
+        assert_eq!(untied.parameters().len(), gpt.parameters().len() + 3 * 8);
+        Ok(())
+    }
+}
@@ -1,3 +1,11 @@
+pub mod attention;
+pub mod block;
+pub mod embedding;
+pub mod gpt;
 pub mod tokenizer;
 
+pub use attention::*;
+pub use block::*;
+pub use embedding::*;
+pub use gpt::*;
 pub use tokenizer::*;
@@ -26,13 +26,13 @@ pub enum Encoding {
 }
 
 impl BytePairEncoder {
+    #[allow(clippy::wrong_self_convention)]
     fn from_utf8(&mut self, input: &str) -> Result<Vec<usize>> {
         let byte_rep = input.as_bytes().to_vec();
         let encoded_byte_rep = byte_rep.into_iter().map(u8::into).collect();
 
         let vocab = input
             .chars()
-            .into_iter()
             .zip(&encoded_byte_rep)
             .map(|x: (char, &usize)| (x.1.to_owned(), x.0.to_string()))
             .collect::<HashMap<usize, String>>();
@@ -42,8 +42,7 @@ impl BytePairEncoder {
 
     fn get_token_id(&mut self) -> usize {
         self.max_token_id += 1;
-        let id = self.max_token_id.clone();
-        id
+        self.max_token_id
     }
 
     pub fn train(
@@ -68,17 +67,15 @@ impl BytePairEncoder {
                     "{}{}",
                     total_vocab
                         .get(&rule.0 .0)
-                        .expect(&format!(
-                            "{:?} exists, current vocab: {:?}",
-                            &rule, total_vocab
-                        ))
+                        .unwrap_or_else(|| {
+                            panic!("{:?} exists, current vocab: {:?}", &rule, total_vocab)
+                        })
                         .as_str(),
                     total_vocab
                         .get(&rule.0 .1)
-                        .expect(&format!(
-                            "{:?} exists, current vocab:  {:?}",
-                            &rule, total_vocab
-                        ))
+                        .unwrap_or_else(|| {
+                            panic!("{:?} exists, current vocab:  {:?}", &rule, total_vocab)
+                        })
                         .as_str()
                 ),
             );
@@ -87,6 +84,11 @@ impl BytePairEncoder {
         Ok(())
     }
 
+    /// One more than the largest token ID, the rows an `Embedding` of these tokens needs.
+    pub fn vocab_size(&self) -> usize {
+        self.max_token_id + 1
+    }
+
     pub fn encode(&mut self, input: &str, encoding: Encoding) -> Result<Vec<usize>> {
         match encoding {
             Encoding::Utf8 => {
@@ -96,12 +98,12 @@ impl BytePairEncoder {
         }
     }
 
-    fn encode_input(&self, input: &Vec<usize>) -> Result<Vec<usize>> {
+    fn encode_input(&self, input: &[usize]) -> Result<Vec<usize>> {
         let mut output: Vec<usize> = Vec::new();
 
-        let mut it = input.into_iter().peekable();
+        let mut it = input.iter().peekable();
         while let Some(token) = it.next() {
-            if !it.peek().is_none() {
+            if it.peek().is_some() {
                 let mut result = self.check_merge_rules_recursively(*token, &mut it)?;
                 output.append(&mut result);
             } else {
@@ -118,7 +120,7 @@ impl BytePairEncoder {
         it: &mut Peekable<I>,
     ) -> Result<Vec<usize>> {
         let mut output: Vec<usize> = Vec::new();
-        if !it.peek().is_none() {
+        if it.peek().is_some() {
             let test = (token, **it.peek().expect("value can be found"));
             match self.merges.get(&test) {
                 Some(value) => {
@@ -164,13 +166,13 @@ impl BytePairEncoder {
                 }
             }
         }
-        if input_vec.len() > 0 {
+        if !input_vec.is_empty() {
             Ok(input_vec)
         } else {
             unreachable!("we checked in the loop");
         }
     }
-    fn train_once(&mut self, input: &Vec<usize>) -> Result<Vec<usize>> {
+    fn train_once(&mut self, input: &[usize]) -> Result<Vec<usize>> {
         let mut counts: HashMap<(usize, usize), usize> = HashMap::new();
 
         let _ = input
@@ -189,7 +191,8 @@ impl BytePairEncoder {
         let encoding_candidate = counts
             // let (top_count, count) = counts
             .iter()
-            .max_by(|a, b| a.1.cmp(&b.1))
+            // break ties on the pair itself so training does not depend on HashMap order
+            .max_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(b.0)))
             .map(|(k, &v)| match v > 1 {
                 true => Some((k, v)),
                 false => None,
@@ -200,9 +203,9 @@ impl BytePairEncoder {
                 let id = self.get_token_id();
                 self.merges.insert(top_count.to_owned(), id);
                 let mut output: Vec<usize> = Vec::new();
-                let mut it = input.into_iter().peekable();
+                let mut it = input.iter().peekable();
                 while let Some(token) = it.next() {
-                    if !it.peek().is_none() {
+                    if it.peek().is_some() {
                         let test = (token.to_owned(), **it.peek().expect("value can be found"));
                         match self.merges.get(&test) {
                             Some(value) => {
@@ -218,7 +221,7 @@ impl BytePairEncoder {
                 }
                 Ok(output)
             }
-            None => Ok(input.clone()),
+            None => Ok(input.to_vec()),
         }
     }
     pub fn decode(&self, input: Vec<usize>) -> Result<String> {
@@ -229,7 +232,7 @@ impl BytePairEncoder {
                 None => unreachable!("the token should exist in vocab!"),
             }
         }
-        return Ok(output);
+        Ok(output)
     }
 
     fn get_merge_rules(&self) -> Vec<(&(usize, usize), &usize)> {